@@ -1,23 +1,4 @@
-def turn_around():
-    turn_left()
-    turn_left()
-    turn_left()
-
-
-def random_turn():
-    if flip(2):
-        if flip(2):
-            turn_left()
-        else:
-            turn_right()
-    else:
-        if flip(2):
-            turn_left()
-            turn_left()
-        else:
-            turn_right()
-            turn_right()
-
+from nav import random_turn, turn_around
 
 has_food = False
 while True:
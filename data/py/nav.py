@@ -0,0 +1,21 @@
+# Movement helpers shared by ants.
+
+def turn_around():
+    turn_left()
+    turn_left()
+    turn_left()
+
+
+def random_turn():
+    if flip(2):
+        if flip(2):
+            turn_left()
+        else:
+            turn_right()
+    else:
+        if flip(2):
+            turn_left()
+            turn_left()
+        else:
+            turn_right()
+            turn_right()
@@ -1,17 +1,4 @@
-def random_turn():
-    if flip(2):
-        if flip(2):
-            turn_left()
-        else:
-            turn_right()
-    else:
-        if flip(2):
-            turn_left()
-            turn_left()
-        else:
-            turn_right()
-            turn_right()
-
+from nav import random_turn
 
 orientation = 0
 target_dir = None
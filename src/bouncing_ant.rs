@@ -13,14 +13,14 @@ fn turn_around() -> AntResult<()> {
 }
 
 fn random_turn() -> AntResult<()> {
-    if call!(flip(2)) {
-        if call!(flip(2)) {
+    if call!(flip(param("two_turns_odds", 2) as u16)) {
+        if call!(flip(param("left_odds", 2) as u16)) {
             call!(turn(LR::Left));
         } else {
             call!(turn(LR::Right));
         }
     } else {
-        if call!(flip(2)) {
+        if call!(flip(param("left_odds", 2) as u16)) {
             call!(turn(LR::Left));
             call!(turn(LR::Left));
         } else {
@@ -58,6 +58,15 @@ fn bouncing_ant() -> AntResult<()> {
     }
 }
 
+pub const TEMPLATE: Template = Template {
+    name: "bouncing",
+    ant: bouncing_ant,
+    params: &[
+        ("two_turns_odds", &[2, 3, 5]),
+        ("left_odds", &[2, 3]),
+    ],
+};
+
 // ENTRY_POINT
 pub fn make_bouncing_ant() {
     let brain = compile(bouncing_ant);
@@ -0,0 +1,192 @@
+// Structural diff of two brains that ignores state renumbering.
+//
+// States are matched by walking both brains from state 0 in lockstep,
+// pairing up the transitions of matched states. States left over are then
+// matched when their instruction and already matched successors agree,
+// and the walk continues from there.
+
+use std::collections::HashMap;
+use crate::neurology::{Instruction, Brain};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Removed(usize),
+    Added(usize),
+    // Matched states whose instructions differ after renumbering, (old, new).
+    Changed(usize, usize),
+}
+
+pub struct BrainDiff {
+    // The new number of each old state.
+    pub old_to_new: Vec<Option<usize>>,
+    pub new_to_old: Vec<Option<usize>>,
+    pub changes: Vec<Change>,
+}
+
+fn targets(insn: &Instruction) -> Vec<usize> {
+    insn.transitions().map(|s| s.0 as usize).collect()
+}
+
+pub fn diff(old: &[Instruction], new: &[Instruction]) -> BrainDiff {
+    let mut d = BrainDiff {
+        old_to_new: vec![None; old.len()],
+        new_to_old: vec![None; new.len()],
+        changes: vec![],
+    };
+    if !old.is_empty() && !new.is_empty() {
+        d.propagate(old, new, 0, 0);
+    }
+
+    let mut by_shape: HashMap<Instruction, Vec<usize>> = HashMap::new();
+    for (b, insn) in new.iter().enumerate() {
+        by_shape.entry(insn.shape()).or_default().push(b);
+    }
+    loop {
+        let mut progress = false;
+        for a in 0..old.len() {
+            if d.old_to_new[a].is_some() {
+                continue;
+            }
+            let ta = targets(&old[a]);
+            let candidates = by_shape.get(&old[a].shape()).map_or(&[][..], |v| &v[..]);
+            let candidate = candidates.iter().copied().find(|&b| {
+                d.new_to_old[b].is_none() &&
+                ta.iter().zip(targets(&new[b])).all(|(&ta, tb)| d.old_to_new[ta] == Some(tb))
+            });
+            if let Some(b) = candidate {
+                d.propagate(old, new, a, b);
+                progress = true;
+            }
+        }
+        if !progress {
+            break;
+        }
+    }
+
+    for (a, b) in d.old_to_new.iter().enumerate() {
+        match *b {
+            None => d.changes.push(Change::Removed(a)),
+            Some(b) => {
+                let same = old[a].transitions().zip(new[b].transitions()).all(|(s, t)| {
+                    match d.old_to_new[s.0 as usize] {
+                        Some(s) => s == t.0 as usize,
+                        None => false,
+                    }
+                });
+                if !same || old[a].shape() != new[b].shape() {
+                    d.changes.push(Change::Changed(a, b));
+                }
+            }
+        }
+    }
+    for (b, a) in d.new_to_old.iter().enumerate() {
+        if a.is_none() {
+            d.changes.push(Change::Added(b));
+        }
+    }
+    d
+}
+
+impl BrainDiff {
+    // Matches a and b, then their successors pairwise, breadth first.
+    fn propagate(&mut self, old: &[Instruction], new: &[Instruction], a: usize, b: usize) {
+        self.old_to_new[a] = Some(b);
+        self.new_to_old[b] = Some(a);
+        let mut queue = std::collections::VecDeque::from(vec![(a, b)]);
+        while let Some((a, b)) = queue.pop_front() {
+            let (ta, tb) = (targets(&old[a]), targets(&new[b]));
+            if ta.len() != tb.len() {
+                continue;
+            }
+            for (ta, tb) in ta.into_iter().zip(tb) {
+                if self.old_to_new[ta].is_none() && self.new_to_old[tb].is_none() {
+                    self.old_to_new[ta] = Some(tb);
+                    self.new_to_old[tb] = Some(ta);
+                    queue.push_back((ta, tb));
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // Like
+    //   ~ 5 -> 6    Move 9 8 ; comment  =>  Move 10 12 ; comment
+    //   - 12        Drop 11 ; comment
+    //   + 14        Flip 2 15 14 ; comment
+    pub fn render(&self, old: &Brain, new: &Brain) -> String {
+        let line = |b: &Brain, i: usize| {
+            let st = &b.states[i];
+            match &st.comment {
+                Some(c) => format!("{} ; {}", st.insn, c),
+                None => st.insn.to_string(),
+            }
+        };
+        let mut res = String::new();
+        for c in &self.changes {
+            res.push_str(&match *c {
+                Change::Removed(a) => format!("- {:<10}  {}", a, line(old, a)),
+                Change::Added(b) => format!("+ {:<10}  {}", b, line(new, b)),
+                Change::Changed(a, b) => format!("~ {:<10}  {}  =>  {}",
+                    format!("{} -> {}", a, b), line(old, a), line(new, b)),
+            });
+            res.push('\n');
+        }
+        let matched = self.old_to_new.iter().filter(|b| b.is_some()).count();
+        let changed = self.changes.iter().filter(|c| matches!(c, Change::Changed(..))).count();
+        res.push_str(&format!(
+            "{} old states, {} new states, {} matched ({} changed), {} removed, {} added\n",
+            self.old_to_new.len(), self.new_to_old.len(), matched, changed,
+            self.old_to_new.len() - matched, self.new_to_old.len() - matched));
+        res
+    }
+}
+
+// ENTRY_POINT
+pub fn brain_diff() {
+    let args: Vec<String> = std::env::args().collect();
+    let (old, new) = match &args[2..] {
+        [old, new] => (old, new),
+        _ => {
+            println!("Usage:");
+            println!("    brain_diff old.ant outputs/spanning.ant");
+            std::process::exit(1);
+        }
+    };
+    let old = Brain::parse(&std::fs::read_to_string(old).unwrap());
+    let new = Brain::parse(&std::fs::read_to_string(new).unwrap());
+    let d = diff(&old.insns(), &new.insns());
+    print!("{}", d.render(&old, &new));
+    if !d.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neurology::parse_ant;
+
+    #[test]
+    fn renumbering() {
+        let old = Brain::parse("Move 1 2\nTurn Left 0\nFlip 3 0 1\n");
+        let new = old.renumber(&[0, 2, 1]);
+        let d = diff(&old.insns(), &new.insns());
+        assert!(d.is_empty());
+        assert_eq!(d.old_to_new, [Some(0), Some(2), Some(1)]);
+
+        let old = parse_ant("Move 1 2\nTurn Left 0\nFlip 3 0 1\nDrop 0\nMark 0 4");
+        let new = parse_ant("Move 3 1\nFlip 3 0 2\nDrop 0\nTurn Right 0\nTurn Left 0");
+        let d = diff(&old, &new);
+        assert_eq!(d.changes, [
+            Change::Changed(1, 3),
+            Change::Changed(2, 1),
+            Change::Removed(4),
+            Change::Added(4),
+        ]);
+        // not reachable in the lockstep walk, matched by its instruction and successor
+        assert_eq!(d.old_to_new[3], Some(2));
+    }
+}
@@ -67,6 +67,21 @@ use crate::number_theory::{
     Random,
 };
 
+use crate::jurisprudence::Rules;
+
+use crate::zobrist;
+
+use crate::psychology::{
+    Mind,
+    Senses,
+};
+
+use crate::history::{
+    Event,
+    EventKind,
+    Observer,
+};
+
 use crate::phenomenology::{
     Marker,
     SenseCondition,
@@ -90,22 +105,63 @@ pub struct World{
     pub y : u8, // but I couldn't be fucked
     pub data : HashMap<Pos, MapToken>,
     pub ant_positions : HashMap<u8, Pos>,
+    pub round : usize, // rounds played so far
+    pub rules : Rules,
+    pub hash : u64, // kept up to date by the mutators, see zobrist.rs
 }
 impl World {
     pub fn round(&mut self, ant_brains : &[Vec<Instruction>; 2], rng : &mut Random) {
+        self.round_observed(ant_brains, rng, &mut ());
+    }
+
+    pub fn round_observed<O>(&mut self, ant_brains : &[Vec<Instruction>; 2], rng : &mut Random, observer : &mut O)
+        where O : Observer + ?Sized
+    {
         // there are two anthills, 91 ants max each
         for id in 0..182 {
-            self.step(id, ant_brains, rng);
+            self.step_observed(id, rng, |ant, _| ant_brains[ant.color as usize][ant.state.0 as usize], observer);
         }
+        self.round += 1;
+    }
+
+    // Like round_observed, for any minds, see psychology.rs.
+    pub fn round_with_minds<O>(&mut self, mut minds : [&mut dyn Mind; 2], rng : &mut Random, observer : &mut O)
+        where O : Observer + ?Sized
+    {
+        for id in 0..182 {
+            self.step_observed(id, rng, |ant, senses| minds[ant.color as usize].decide(ant, senses), observer);
+        }
+        self.round += 1;
     }
 
     pub fn step(&mut self, id : u8, ant_brains : &[Vec<Instruction>; 2], rng : &mut Random) {
+        self.step_with(id, rng, |ant| ant_brains[ant.color as usize][ant.state.0 as usize]);
+    }
+
+    // Like step, but the instruction comes from the brain function,
+    // which is only called if the ant is alive and not resting.
+    pub fn step_with<F>(&mut self, id : u8, rng : &mut Random, brain : F)
+        where F : FnOnce(&Ant) -> Instruction
+    {
+        self.step_observed(id, rng, |ant, _| brain(ant), &mut ());
+    }
+
+    // Like step_with, but the brain can also sense around the ant,
+    // and the observer is told what happened.
+    pub fn step_observed<F, O>(&mut self, id : u8, rng : &mut Random, brain : F, observer : &mut O)
+        where F : FnOnce(&Ant, &Senses) -> Instruction, O : Observer + ?Sized
+    {
         if let Some(pos) = self.find_ant(id) {
             if let Some(ant) = self.ant_at(pos) {
                 if ant.resting > 0 {
                     self.set_ant_at(pos, Ant { resting : ant.resting-1, ..ant })
                 } else {
-                    match ant_brains[ant.color as usize][ant.state.0 as usize] {
+                    let round = self.round;
+                    let mut notify = |pos, kind| observer.observe(Event {
+                        round, ant_id : ant.id, color : ant.color, pos, kind,
+                    });
+                    let insn = brain(&ant, &Senses { world : self, pos, ant : &ant });
+                    match insn {
                         Sense(sdir, s1, s2, cond) => {
                             if let Some(sensed_pos) = sensed_cell(pos, ant.direction, sdir) {
                                 let state = if self.cell_matches(sensed_pos, cond, ant.color) { s1 } else { s2 };
@@ -113,11 +169,15 @@ impl World {
                             }
                         },
                         Mark(i, state) => {
-                            self.set_marker_at(pos, ant.color, i);
+                            if self.set_marker_at(pos, ant.color, i) {
+                                notify(pos, EventKind::Marked(i));
+                            }
                             self.set_ant_at(pos, Ant { state, ..ant });
                         },
                         Unmark(i, state) => {
-                            self.clear_marker_at(pos, ant.color, i);
+                            if self.clear_marker_at(pos, ant.color, i) {
+                                notify(pos, EventKind::Unmarked(i));
+                            }
                             self.set_ant_at(pos, Ant { state, ..ant });
                         },
                         PickUp(s1, s2) => {
@@ -125,12 +185,18 @@ impl World {
                             if ant.has_food || food_amount == 0 {
                                 self.set_ant_at(pos, Ant { state : s2, ..ant });
                             } else {
+                                notify(pos, EventKind::PickedUp);
                                 self.set_food_at(pos, Food(food_amount-1));
                                 self.set_ant_at(pos, Ant { state : s1, has_food : true, ..ant });
                             }
                         },
                         Drop(state) => {
                             if ant.has_food {
+                                notify(pos, if self.anthill_at(pos, ant.color) {
+                                    EventKind::FoodDeposited
+                                } else {
+                                    EventKind::Dropped
+                                });
                                 let food_amount = self.food_at(pos).0;
                                 self.set_food_at(pos, Food(food_amount + 1));
                             }
@@ -142,14 +208,17 @@ impl World {
                         Move(s1,s2) => {
                             if let Some(new_pos) = adj(pos, ant.direction) {
                                 if self.rocky(new_pos) || self.some_ant_is_at(new_pos) {
+                                    notify(pos, EventKind::Blocked);
                                     self.set_ant_at(pos, Ant { state : s2, ..ant });
                                 } else {
+                                    notify(new_pos, EventKind::Moved { from : pos });
                                     self.clear_ant_at(pos);
-                                    self.set_ant_at(new_pos, Ant { state : s1, resting : 14, ..ant });
+                                    self.set_ant_at(new_pos, Ant { state : s1, resting : self.rules.rest_after_move, ..ant });
                                     self.ant_positions.insert(ant.id, new_pos);
-                                    self.check_for_surrounded_ants(new_pos);
+                                    self.check_for_surrounded_ants(new_pos, observer);
                                 }
                             } else {
+                                notify(pos, EventKind::Blocked);
                                 self.set_ant_at(pos, Ant { state : s2, ..ant });
                             }
                         },
@@ -196,7 +265,7 @@ impl World {
     }
 
     pub fn new() -> World {
-        World{ x: 0, y: 0, data: HashMap::new(), ant_positions: HashMap::new() }
+        World{ x: 0, y: 0, data: HashMap::new(), ant_positions: HashMap::new(), round: 0, rules: Rules::default(), hash: 0 }
     }
 
     pub fn framed(x : u8, y : u8) -> World {
@@ -210,12 +279,13 @@ impl World {
             h.insert(Pos{ x: 0    , y: cy}, MapToken::Rock);
             h.insert(Pos{ x: x - 1, y: cy}, MapToken::Rock);
         }
-        World{ x, y, data: h, ant_positions: HashMap::new() }
+        World{ x, y, data: h, ant_positions: HashMap::new(), round: 0, rules: Rules::default(), hash: 0 }
     }
 
     pub fn from_map_string(map: &str) -> World {
-        let (rest, world) = World::parse(map).unwrap();
+        let (rest, mut world) = World::parse(map).unwrap();
         assert_eq!(rest, "\n");
+        world.hash = zobrist::world_hash(&world);
         world
     }
 
@@ -289,22 +359,29 @@ impl World {
         count
     }
 
-    pub fn check_for_surrounded_ant_at(&mut self, p : Pos) {
+    pub fn check_for_surrounded_ant_at<O : Observer + ?Sized>(&mut self, p : Pos, observer : &mut O) {
         if let Some(ant) = self.ant_at(p) {
-            if self.surrounding_ants_amount(p, other_color(ant.color)) >= 5 {
+            if self.surrounding_ants_amount(p, other_color(ant.color)) >= self.rules.kill_threshold {
+                observer.observe(Event {
+                    round : self.round,
+                    ant_id : ant.id,
+                    color : ant.color,
+                    pos : p,
+                    kind : EventKind::Killed,
+                });
                 self.clear_ant_at(p);
                 self.ant_positions.remove(&ant.id);
                 let food_amount = self.food_at(p).0;
-                self.set_food_at(p, Food(food_amount + 3 + if ant.has_food { 1 } else { 0 } ));
+                self.set_food_at(p, Food(food_amount + self.rules.food_on_death + if ant.has_food { 1 } else { 0 } ));
             }
         }
     }
 
-    pub fn check_for_surrounded_ants(&mut self, p : Pos) {
-        self.check_for_surrounded_ant_at(p);
+    pub fn check_for_surrounded_ants<O : Observer + ?Sized>(&mut self, p : Pos, observer : &mut O) {
+        self.check_for_surrounded_ant_at(p, observer);
         for d in simple_enum_iter::<Dir>(6) {
             if let Some(a) = adj(p,d) {
-                self.check_for_surrounded_ant_at(a);
+                self.check_for_surrounded_ant_at(a, observer);
             }
         }
     }
@@ -325,6 +402,9 @@ impl World {
     }
 
     pub fn check_marker_at(&self, p : Pos, c : Color, m : Marker) -> bool {
+        if m.0 >= self.rules.num_markers {
+            return false;
+        }
         if let Some(Clear(Contents { markers, .. } )) = self.data.get(&p) {
             if let Some(cms) = markers.0.get(&c) {
                 return cms.get(m.0);
@@ -377,36 +457,65 @@ impl World {
 
     pub fn set_ant_at(&mut self, p : Pos, a : Ant) {
         if let Some(Clear(t)) = self.data.get_mut(&p) {
+            if let Some(old) = &t.ant {
+                self.hash ^= zobrist::ant_key(p, old);
+            }
+            self.hash ^= zobrist::ant_key(p, &a);
             t.ant = Some(a);
         }
     }
 
     pub fn clear_ant_at(&mut self, p : Pos) {
         if let Some(Clear(t)) = self.data.get_mut(&p) {
+            if let Some(old) = &t.ant {
+                self.hash ^= zobrist::ant_key(p, old);
+            }
             t.ant = None;
         }
     }
 
     pub fn set_food_at(&mut self, p : Pos, f : Food) {
         if let Some(Clear(t)) = self.data.get_mut(&p) {
+            self.hash ^= zobrist::food_key(p, t.food) ^ zobrist::food_key(p, f);
             t.food = f;
         }
     }
 
-    pub fn set_marker_at(&mut self, p : Pos, c : Color, m : Marker) {
+    // Both return whether the marker actually changed.
+    pub fn set_marker_at(&mut self, p : Pos, c : Color, m : Marker) -> bool {
+        if m.0 >= self.rules.num_markers {
+            return false;
+        }
         if let Some(Clear(Contents { markers, .. } )) = self.data.get_mut(&p) {
             if let Some(cms) = markers.0.get_mut(&c) {
-                cms.set(m.0, true);
+                if !cms.set(m.0, true) {
+                    self.hash ^= zobrist::marker_key(p, c, m);
+                    return true;
+                }
             }
         }
+        false
     }
 
-    pub fn clear_marker_at(&mut self, p : Pos, c : Color, m : Marker) {
+    pub fn clear_marker_at(&mut self, p : Pos, c : Color, m : Marker) -> bool {
+        if m.0 >= self.rules.num_markers {
+            return false;
+        }
         if let Some(Clear(Contents { markers, .. } )) = self.data.get_mut(&p) {
             if let Some(cms) = markers.0.get_mut(&c) {
-                cms.set(m.0, false);
+                if cms.set(m.0, false) {
+                    self.hash ^= zobrist::marker_key(p, c, m);
+                    return true;
+                }
             }
         }
+        false
+    }
+
+    // In the .world format. Ants and markers aren't part of a map,
+    // and food on an anthill shows as just food.
+    pub fn to_map_string(&self) -> String {
+        format!("{}\n{}\n{}", self.x, self.y, self)
     }
 
     pub fn ant_is_alive(&self, id : u8) -> bool {
@@ -498,9 +607,7 @@ fn fold_map_tokens_x(
     ants_counter : &mut u8
 )
 {
-    let mut cx = 0;
-    for x in xs {
-        cx += 1;
+    for (cx, x) in (1..).zip(xs) {
         let p = Pos{ x: cx, y: *y };
         if let Clear(xx) = x {
             if let Contents{ant : Some(a), ..} = xx {
@@ -687,7 +794,7 @@ pub fn cartography_time_workload() {
     let mut rng = Random::new(12345);
     // full match
     let time = Instant::now();
-    for _ in 0..100000 {
+    for _ in 0..w.rules.rounds {
         w.round(&ant_brains, &mut rng);
     }
     println!("Elapsed time for full match: {:?}", time.elapsed());
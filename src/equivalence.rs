@@ -0,0 +1,147 @@
+// Checks whether two brains behave the same.
+//
+// Both brains are driven by the same outcomes: whether a sense, move or
+// pickup succeeded and what a flip came up with. They are equivalent when
+// every sequence of outcomes makes them execute the same instructions,
+// up to state numbers. The product of the two state graphs is explored
+// breadth first, so the counterexample, if any, is a shortest one.
+
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
+use crate::neurology::{Instruction, Brain};
+
+// (old state, new state, index of the transition taken)
+pub type Step = (usize, usize, usize);
+
+pub struct Counterexample {
+    pub path: Vec<Step>,
+    // The states where the brains do different things.
+    pub old_state: usize,
+    pub new_state: usize,
+}
+
+fn successors(insn: &Instruction) -> Vec<usize> {
+    match *insn {
+        // always takes the first transition
+        Instruction::Flip(1, st, _) => vec![st.0 as usize],
+        _ => insn.transitions().map(|s| s.0 as usize).collect(),
+    }
+}
+
+// An empty brain only matches another empty one.
+pub fn check(old: &[Instruction], new: &[Instruction]) -> Result<(), Counterexample> {
+    match (old.is_empty(), new.is_empty()) {
+        (true, true) => return Ok(()),
+        (false, false) => {}
+        _ => return Err(Counterexample { path: vec![], old_state: 0, new_state: 0 }),
+    }
+    let mut parent: HashMap<(usize, usize), Option<Step>> = HashMap::new();
+    parent.insert((0, 0), None);
+    let mut queue = VecDeque::from(vec![(0, 0)]);
+    while let Some((a, b)) = queue.pop_front() {
+        if old[a].shape() != new[b].shape() {
+            let mut path = vec![];
+            let mut cur = (a, b);
+            while let Some(p) = parent[&cur] {
+                path.push(p);
+                cur = (p.0, p.1);
+            }
+            path.reverse();
+            return Err(Counterexample { path, old_state: a, new_state: b });
+        }
+        for (i, next) in successors(&old[a]).into_iter().zip(successors(&new[b])).enumerate() {
+            if let Entry::Vacant(e) = parent.entry(next) {
+                e.insert(Some((a, b, i)));
+                queue.push_back(next);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Counterexample {
+    pub fn render(&self, old: &Brain, new: &Brain) -> String {
+        let outcome = |insn: &Instruction, i: usize| match (insn, i) {
+            (Instruction::Flip(..), 0) => "0".to_string(),
+            (Instruction::Flip(p, ..), _) => format!("1..{}", p - 1),
+            (_, 0) if insn.transitions().count() == 1 => "done".to_string(),
+            (_, 0) => "yes".to_string(),
+            _ => "no".to_string(),
+        };
+        let mut res = String::new();
+        for &(a, b, i) in &self.path {
+            let insn = old.states[a].insn;
+            res.push_str(&format!("{:>6} {:>6}  {:<30} -> {}\n",
+                a, b, insn.shape().to_string(), outcome(&insn, i)));
+        }
+        for &(what, brain, st) in &[("old", old, self.old_state), ("new", new, self.new_state)] {
+            let st_ = match brain.states.get(st) {
+                Some(st_) => st_,
+                None => {
+                    res.push_str(&format!("{} brain is empty\n", what));
+                    continue;
+                }
+            };
+            res.push_str(&format!("{} state {}: {}", what, st, st_.insn));
+            if let Some(c) = &st_.comment {
+                res.push_str(&format!(" ; {}", c));
+            }
+            res.push('\n');
+        }
+        res
+    }
+}
+
+// ENTRY_POINT
+pub fn brain_equiv() {
+    let args: Vec<String> = std::env::args().collect();
+    let (old, new) = match &args[2..] {
+        [old, new] => (old, new),
+        _ => {
+            println!("Usage:");
+            println!("    brain_equiv outputs/spanning.ant outputs/spanning.py.ant");
+            std::process::exit(1);
+        }
+    };
+    let old = Brain::parse(&std::fs::read_to_string(old).unwrap());
+    let new = Brain::parse(&std::fs::read_to_string(new).unwrap());
+    match check(&old.insns(), &new.insns()) {
+        Ok(()) => println!("equivalent"),
+        Err(ce) => {
+            println!("not equivalent, shortest distinguishing run (old state, new state, instruction, outcome):");
+            print!("{}", ce.render(&old, &new));
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neurology::parse_ant;
+
+    #[test]
+    fn equivalence() {
+        let brain = Brain::parse("Move 1 2\nTurn Left 0\nFlip 3 0 1\n");
+        assert!(check(&brain.insns(), &brain.renumber(&[0, 2, 1]).insns()).is_ok());
+
+        // the loop unrolled once
+        let old = parse_ant("Move 0 1\nTurn Left 0");
+        assert!(check(&old, &parse_ant("Move 2 1\nTurn Left 0\nMove 0 1")).is_ok());
+        // Flip 1 never takes the second transition
+        assert!(check(&parse_ant("Flip 1 0 1\nDrop 0"), &parse_ant("Flip 1 0 1\nMove 0 0")).is_ok());
+
+        let ce = check(&old, &parse_ant("Move 0 1\nTurn Left 2\nMove 0 3\nTurn Right 0"))
+            .err().unwrap();
+        assert_eq!(ce.path, [(0, 0, 1), (1, 1, 0), (0, 2, 1)]);
+        assert_eq!((ce.old_state, ce.new_state), (1, 3));
+
+        let ce = check(&parse_ant("Flip 2 0 0"), &parse_ant("Flip 3 0 0")).err().unwrap();
+        assert!(ce.path.is_empty());
+
+        assert!(check(&[], &[]).is_ok());
+        let empty = Brain::parse("");
+        let ce = check(&empty.insns(), &brain.insns()).err().unwrap();
+        assert!(ce.path.is_empty());
+        assert_eq!(ce.render(&empty, &brain), "old brain is empty\nnew state 0: Move 1 2\n");
+    }
+}
@@ -0,0 +1,281 @@
+// Genetic optimiser for brains.
+//
+// Starts from a population of copies of the given brain, then every
+// generation keeps the fittest ones and fills the rest with mutated
+// crossovers of them. Mutations tweak Flip probabilities, turn directions
+// and markers, and rewire transitions to other existing states, so every
+// brain stays valid. Fitness is the score from tournament::match_pair
+// against every opponent on every world, with fixed match seeds, so a run
+// is reproducible given its own --seed.
+//
+// The best brains seen so far are kept in outputs/hall_of_fame/,
+// and are added to the initial population of the next run.
+
+use std::path::Path;
+use crate::cartography::World;
+use crate::neurology::{Instruction, State, Brain, Marker, SenseCondition, LR};
+use crate::number_theory::Random;
+use crate::tournament::{MatchScore, match_pair, sample_worlds};
+
+const HALL_OF_FAME_DIR: &str = "outputs/hall_of_fame";
+const HALL_OF_FAME_SIZE: usize = 10;
+
+fn random_idx(rng: &mut Random, n: usize) -> usize {
+    assert!(n > 0 && n <= 0x4000, "{}", n);
+    rng.next(n as u32) as usize
+}
+
+// Applies one random mutation.
+pub fn mutate(brain: &mut Brain, rng: &mut Random) {
+    let n = brain.states.len();
+    let i = random_idx(rng, n);
+    let insn = &mut brain.states[i].insn;
+    match (random_idx(rng, 3), insn) {
+        (0, Instruction::Flip(p, ..)) => {
+            *p = match random_idx(rng, 4) {
+                0 => p.saturating_add(1),
+                1 => p.saturating_sub(1).max(1),
+                2 => p.saturating_mul(2),
+                _ => (*p / 2).max(1),
+            };
+        }
+        (0, Instruction::Turn(lr, _)) => {
+            *lr = match lr {
+                LR::Left => LR::Right,
+                LR::Right => LR::Left,
+            };
+        }
+        (0, Instruction::Mark(m, _)) |
+        (0, Instruction::Unmark(m, _)) |
+        (0, Instruction::Sense(_, _, _, SenseCondition::Marker(m))) => {
+            *m = Marker::new((m.0 + 1 + random_idx(rng, 5)) % 6);
+        }
+        (_, insn) => {
+            let k = random_idx(rng, insn.transitions().count());
+            let target = State(random_idx(rng, n) as u16);
+            *insn.transitions_mut().nth(k).unwrap() = target;
+        }
+    }
+}
+
+// Copies a random range of states from b into a,
+// transitions that don't fit are wrapped around.
+pub fn crossover(a: &Brain, b: &Brain, rng: &mut Random) -> Brain {
+    let mut child = a.clone();
+    let n = a.states.len().min(b.states.len());
+    let start = random_idx(rng, n);
+    let end = start + 1 + random_idx(rng, n - start);
+    for i in start..end {
+        let mut insn = b.states[i].insn;
+        for s in insn.transitions_mut() {
+            *s = State((s.0 as usize % a.states.len()) as u16);
+        }
+        child.states[i].insn = insn;
+    }
+    child
+}
+
+pub struct Arena {
+    worlds: Vec<(World, u32)>,
+    opponents: Vec<Vec<Instruction>>,
+}
+
+impl Arena {
+    // The first num_worlds sample worlds, each with its own fixed match seed.
+    pub fn new(num_worlds: usize, opponent_paths: &[String]) -> Self {
+        Arena {
+            worlds: sample_worlds().iter().take(num_worlds).enumerate().map(|(i, path)| {
+                let w = World::from_map_string(&std::fs::read_to_string(path).unwrap());
+                (w, 1000 + i as u32)
+            }).collect(),
+            opponents: opponent_paths.iter()
+                .map(|path| Brain::parse(&std::fs::read_to_string(path).unwrap()).insns())
+                .collect(),
+        }
+    }
+
+    // 4 points per opponent per world, see match_pair().
+    pub fn max_fitness(&self) -> usize {
+        4 * self.worlds.len() * self.opponents.len()
+    }
+
+    pub fn fitness(&self, brain: &Brain) -> usize {
+        let mut score = MatchScore::new();
+        for (world, seed) in &self.worlds {
+            for opponent in &self.opponents {
+                let mut world = world.clone();
+                let mut brains = [brain.insns(), opponent.clone()];
+                match_pair(&mut score, &mut world, &mut brains, &mut Random::new(*seed));
+            }
+        }
+        score.0
+    }
+}
+
+struct Individual {
+    brain: Brain,
+    fitness: usize,
+    generation: usize,
+}
+
+fn load_hall_of_fame() -> Vec<Brain> {
+    let mut res = vec![];
+    if let Ok(dir) = std::fs::read_dir(HALL_OF_FAME_DIR) {
+        let mut paths: Vec<_> = dir.map(|p| p.unwrap().path()).collect();
+        paths.sort();
+        for p in paths {
+            if p.extension().is_some_and(|e| e == "ant") {
+                res.push(Brain::parse(&std::fs::read_to_string(p).unwrap()));
+            }
+        }
+    }
+    res
+}
+
+fn save_hall_of_fame(hall_of_fame: &[Individual], max_fitness: usize) {
+    std::fs::create_dir_all(HALL_OF_FAME_DIR).unwrap();
+    for (rank, ind) in hall_of_fame.iter().enumerate() {
+        let mut brain = ind.brain.clone();
+        brain.states[0].leading = vec![format!(
+            "; fitness {} of {}, generation {}", ind.fitness, max_fitness, ind.generation)];
+        let path = Path::new(HALL_OF_FAME_DIR).join(format!("{:02}.ant", rank));
+        std::fs::write(path, brain.dumps()).unwrap();
+    }
+    for rank in hall_of_fame.len()..HALL_OF_FAME_SIZE {
+        let _ = std::fs::remove_file(Path::new(HALL_OF_FAME_DIR).join(format!("{:02}.ant", rank)));
+    }
+}
+
+fn add_to_hall_of_fame(hall_of_fame: &mut Vec<Individual>, ind: &Individual) {
+    if hall_of_fame.iter().any(|h| h.brain.insns() == ind.brain.insns()) {
+        return;
+    }
+    hall_of_fame.push(Individual {
+        brain: ind.brain.clone(),
+        fitness: ind.fitness,
+        generation: ind.generation,
+    });
+    // stable, so earlier finds win ties
+    hall_of_fame.sort_by_key(|h| std::cmp::Reverse(h.fitness));
+    hall_of_fame.truncate(HALL_OF_FAME_SIZE);
+}
+
+// Picks the fitter of two random individuals.
+fn select<'a>(population: &'a [Individual], rng: &mut Random) -> &'a Individual {
+    let a = &population[random_idx(rng, population.len())];
+    let b = &population[random_idx(rng, population.len())];
+    if b.fitness > a.fitness { b } else { a }
+}
+
+// ENTRY_POINT
+pub fn evolve() {
+    let args: Vec<String> = std::env::args().collect();
+    fn usage() -> ! {
+        println!("Usage:");
+        println!("    evolve [--seed N] [--generations N] [--population N] [--worlds N] start.ant opponent.ant...");
+        println!("Plays on the first N sample worlds (default 1), keeps the best brains in {}.", HALL_OF_FAME_DIR);
+        std::process::exit(1);
+    }
+    let mut seed = 12345;
+    let mut generations = 10;
+    let mut population_size = 8;
+    let mut num_worlds = 1;
+    let mut rest = &args[2..];
+    let (start, opponents) = loop {
+        match rest {
+            [flag, n, tail @ ..] if flag.starts_with("--") => {
+                let n = n.parse().unwrap_or_else(|_| usage());
+                match flag.as_str() {
+                    "--seed" => seed = n as u32,
+                    "--generations" => generations = n,
+                    "--population" => population_size = n,
+                    "--worlds" => num_worlds = n,
+                    _ => usage(),
+                }
+                rest = tail;
+            }
+            [start, opponents @ ..] if !opponents.is_empty() => break (start, opponents),
+            _ => usage(),
+        }
+    };
+    if population_size < 2 {
+        usage();
+    }
+
+    let arena = Arena::new(num_worlds, opponents);
+    let mut rng = Random::new(seed);
+
+    let start = Brain::parse(&std::fs::read_to_string(start).unwrap());
+    let mut brains = load_hall_of_fame();
+    brains.truncate(population_size - 1);
+    brains.insert(0, start.clone());
+    while brains.len() < population_size {
+        let mut brain = start.clone();
+        mutate(&mut brain, &mut rng);
+        brains.push(brain);
+    }
+
+    let mut hall_of_fame: Vec<Individual> = vec![];
+    for generation in 0..generations {
+        let mut population: Vec<Individual> = brains.into_iter().map(|brain| {
+            let fitness = arena.fitness(&brain);
+            Individual { brain, fitness, generation }
+        }).collect();
+        population.sort_by_key(|ind| std::cmp::Reverse(ind.fitness));
+        for ind in &population {
+            add_to_hall_of_fame(&mut hall_of_fame, ind);
+        }
+        save_hall_of_fame(&hall_of_fame, arena.max_fitness());
+        let fitnesses: Vec<usize> = population.iter().map(|ind| ind.fitness).collect();
+        println!("generation {}: fitness {:?} of {}", generation, fitnesses, arena.max_fitness());
+
+        // the top half survives, the rest are their children
+        population.truncate(population_size.div_ceil(2));
+        brains = population.iter().map(|ind| ind.brain.clone()).collect();
+        while brains.len() < population_size {
+            let a = select(&population, &mut rng);
+            let b = select(&population, &mut rng);
+            let mut child = crossover(&a.brain, &b.brain, &mut rng);
+            for _ in 0..1 + random_idx(&mut rng, 3) {
+                mutate(&mut child, &mut rng);
+            }
+            brains.push(child);
+        }
+    }
+    println!("best: fitness {} of {}, saved to {}/00.ant",
+        hall_of_fame[0].fitness, arena.max_fitness(), HALL_OF_FAME_DIR);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutations_keep_brains_valid() {
+        let start = Brain::parse(&std::fs::read_to_string("data/sample.ant").unwrap());
+        let run = |seed| {
+            let mut rng = Random::new(seed);
+            let mut brains = vec![start.clone()];
+            for _ in 0..200 {
+                let a = &brains[random_idx(&mut rng, brains.len())];
+                let b = &brains[random_idx(&mut rng, brains.len())];
+                let mut child = crossover(a, b, &mut rng);
+                mutate(&mut child, &mut rng);
+                brains.push(child);
+            }
+            brains
+        };
+        let brains = run(42);
+        for brain in &brains {
+            assert_eq!(brain.states.len(), start.states.len());
+            for st in &brain.states {
+                assert!(st.insn.transitions().all(|s| (s.0 as usize) < start.states.len()));
+                if let Instruction::Flip(p, ..) = st.insn {
+                    assert!(p >= 1);
+                }
+            }
+        }
+        assert!(brains.iter().any(|b| b.insns() != start.insns()));
+        assert_eq!(run(42), brains);
+    }
+}
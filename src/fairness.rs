@@ -0,0 +1,236 @@
+// How much a world favours red or black, before it goes into the pool.
+//
+// Distances are steps around rocks from the nearest cell of the hill,
+// see topography.rs. Food is counted in units, so a 9 counts nine times.
+// Choke points are cells every shortest path between the hills goes
+// through, whoever is closer to one can block it sooner.
+
+use std::collections::BTreeMap;
+
+use crate::biology::Color;
+use crate::biology::Color::*;
+use crate::cartography::World;
+use crate::geometry::Pos;
+use crate::topography::{DistanceField, food_cells};
+
+const RADII : &[usize] = &[10, 20, 40, 80];
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct ColorReport {
+    pub hill_cells: usize,
+    pub food_within: Vec<(usize, usize)>,  // (distance, food units)
+    pub reachable_food: usize,
+    pub mean_food_distance: Option<f64>,
+    pub median_food_distance: Option<usize>,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct ChokePoint {
+    pub x: u8,
+    pub y: u8,
+    pub red_distance: usize,
+    pub black_distance: usize,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+pub struct FairnessReport {
+    pub red: ColorReport,
+    pub black: ColorReport,
+    pub hill_distance: Option<usize>,
+    pub choke_points: Vec<ChokePoint>,
+    pub asymmetry: f64,  // from -1 to 1, positive favours red
+}
+
+fn color_report(world : &World, field : &DistanceField, c : Color) -> ColorReport {
+    // (distance, units), nearest first
+    let mut food : Vec<(usize, usize)> = food_cells(world).into_iter()
+        .filter_map(|p| field.get(p).map(|d| (d, world.food_at(p).0 as usize)))
+        .collect();
+    food.sort();
+    let total : usize = food.iter().map(|&(_, units)| units).sum();
+
+    let mut median_food_distance = None;
+    let mut seen = 0;
+    for &(d, units) in &food {
+        seen += units;
+        if 2 * seen >= total {
+            median_food_distance = Some(d);
+            break;
+        }
+    }
+    ColorReport {
+        hill_cells: world.data.keys().filter(|&&p| world.anthill_at(p, c)).count(),
+        food_within: RADII.iter().map(|&r| {
+            (r, food.iter().filter(|&&(d, _)| d <= r).map(|&(_, units)| units).sum())
+        }).collect(),
+        reachable_food: total,
+        mean_food_distance: if total == 0 { None } else {
+            Some(food.iter().map(|&(d, units)| (d * units) as f64).sum::<f64>() / total as f64)
+        },
+        median_food_distance,
+    }
+}
+
+// Cells on shortest paths between the hills,
+// at a distance from the red hill where there's only one such cell.
+fn choke_points(red : &DistanceField, black : &DistanceField, hill_distance : usize) -> Vec<ChokePoint> {
+    let mut levels : BTreeMap<usize, Vec<Pos>> = BTreeMap::new();
+    for (&p, &dr) in &red.0 {
+        if let Some(db) = black.get(p) {
+            if dr > 0 && db > 0 && dr + db == hill_distance {
+                levels.entry(dr).or_default().push(p);
+            }
+        }
+    }
+    levels.into_iter()
+        .filter(|(_, cells)| cells.len() == 1)
+        .map(|(dr, cells)| ChokePoint {
+            x: cells[0].x,
+            y: cells[0].y,
+            red_distance: dr,
+            black_distance: hill_distance - dr,
+        })
+        .collect()
+}
+
+// From -1 (all b) to 1 (all a), 0 if both are 0.
+fn advantage(a : f64, b : f64) -> f64 {
+    if a + b == 0.0 { 0.0 } else { (a - b) / (a + b) }
+}
+
+pub fn analyse(world : &World) -> FairnessReport {
+    let red_field = DistanceField::from_anthill(world, Red);
+    let black_field = DistanceField::from_anthill(world, Black);
+    let red = color_report(world, &red_field, Red);
+    let black = color_report(world, &black_field, Black);
+
+    let hill_distance = world.data.keys()
+        .filter(|&&p| world.anthill_at(p, Black))
+        .filter_map(|&p| red_field.get(p))
+        .min();
+    let choke_points = match hill_distance {
+        Some(d) => choke_points(&red_field, &black_field, d),
+        None => vec![],
+    };
+
+    // the average advantage over all the numbers above
+    let mut advantages = vec![];
+    for (&(_, r), &(_, b)) in red.food_within.iter().zip(&black.food_within) {
+        advantages.push(advantage(r as f64, b as f64));
+    }
+    advantages.push(advantage(red.reachable_food as f64, black.reachable_food as f64));
+    advantages.push(match (red.mean_food_distance, black.mean_food_distance) {
+        (Some(r), Some(b)) => advantage(b, r),
+        (Some(_), None) => 1.0,
+        (None, Some(_)) => -1.0,
+        (None, None) => 0.0,
+    });
+    let closer_to_red = choke_points.iter().filter(|c| c.red_distance < c.black_distance).count();
+    let closer_to_black = choke_points.iter().filter(|c| c.black_distance < c.red_distance).count();
+    advantages.push(advantage(closer_to_red as f64, closer_to_black as f64));
+    let asymmetry = advantages.iter().sum::<f64>() / advantages.len() as f64;
+
+    FairnessReport { red, black, hill_distance, choke_points, asymmetry }
+}
+
+pub fn render(report : &FairnessReport) -> String {
+    let mut res = String::new();
+    let opt = |x : Option<String>| x.unwrap_or_else(|| "-".to_string());
+    for (name, r) in &[("red", &report.red), ("black", &report.black)] {
+        res.push_str(&format!("{}: {} hill cells, {} reachable food\n", name, r.hill_cells, r.reachable_food));
+        let within : Vec<String> = r.food_within.iter().map(|(d, units)| format!("{} within {}", units, d)).collect();
+        res.push_str(&format!("    food: {}\n", within.join(", ")));
+        res.push_str(&format!("    distance to food: mean {}, median {}\n",
+            opt(r.mean_food_distance.map(|m| format!("{:.1}", m))),
+            opt(r.median_food_distance.map(|m| m.to_string()))));
+    }
+    res.push_str(&format!("hill distance: {}\n", opt(report.hill_distance.map(|d| d.to_string()))));
+    let chokes : Vec<String> = report.choke_points.iter()
+        .map(|c| format!("({}, {}) {}:{}", c.x, c.y, c.red_distance, c.black_distance))
+        .collect();
+    res.push_str(&format!("choke points (red:black distance): {}\n",
+        if chokes.is_empty() { "none".to_string() } else { chokes.join(", ") }));
+    let favours = if report.asymmetry > 0.0 { " (favours red)" }
+        else if report.asymmetry < 0.0 { " (favours black)" }
+        else { "" };
+    res.push_str(&format!("asymmetry: {:+.3}{}\n", report.asymmetry, favours));
+    res
+}
+
+// ENTRY_POINT
+pub fn map_fairness() {
+    let args: Vec<String> = std::env::args().collect();
+    let (path, json) = match &args[2..] {
+        [path] => (path, false),
+        [flag, path] if flag == "--json" => (path, true),
+        _ => {
+            println!("Usage:");
+            println!("    map_fairness [--json] map.world");
+            std::process::exit(1);
+        }
+    };
+    let world = World::from_map_string(&std::fs::read_to_string(path).unwrap());
+    let report = analyse(&world);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        print!("{}", render(&report));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetric_world_is_fair() {
+        // tiny.world looks the same turned around, with the colours swapped
+        let world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let report = analyse(&world);
+        assert_eq!(report.red, report.black);
+        assert_eq!(report.asymmetry, 0.0);
+        assert_eq!(report.red.reachable_food, 9 * 6 + 3 * 4 + 5 * 2);
+        assert!(report.choke_points.iter().all(|c| c.red_distance + c.black_distance == report.hill_distance.unwrap()));
+    }
+
+    #[test]
+    fn walled_in_red() {
+        let world = World::from_map_string("\
+7
+5
+# # # # # # #
+ # + . # 5 . #
+# . . # # . #
+ # . . # 3 - #
+# # # # # # #
+");
+        let report = analyse(&world);
+        assert_eq!(report.red.reachable_food, 0);
+        assert_eq!(report.red.mean_food_distance, None);
+        assert_eq!(report.black.reachable_food, 8);
+        assert_eq!(report.black.food_within[0], (10, 8));
+        assert_eq!(report.black.mean_food_distance, Some((3.0 * 1.0 + 5.0 * 2.0) / 8.0));
+        assert_eq!(report.black.median_food_distance, Some(2));
+        assert_eq!(report.hill_distance, None);
+        assert!(report.asymmetry < -0.5);
+        assert!(render(&report).contains("favours black"));
+    }
+
+    #[test]
+    fn corridor() {
+        // the only way between the hills is through (3, 2)
+        let world = World::from_map_string("\
+7
+5
+# # # # # # #
+ # + . # . - #
+# . . . . . #
+ # . . # . . #
+# # # # # # #
+");
+        let report = analyse(&world);
+        assert!(report.choke_points.iter().any(|c| (c.x, c.y) == (3, 2)));
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""x":3,"y":2"#), "{}", json);
+    }
+}
@@ -0,0 +1,166 @@
+// Property tests of the simulator on random small worlds and random brains.
+//
+// Every round the world has to stay consistent with itself, and
+// World::round has to agree with World::round_with_minds playing the
+// same tables through the psychology::Mind trait.
+
+use quickcheck::{Arbitrary, Gen};
+use quickcheck_macros::quickcheck;
+use rand::Rng;
+use std::collections::HashSet;
+
+use crate::cartography::World;
+use crate::geography::MapToken::Clear;
+use crate::jurisprudence::Rules;
+use crate::neurology::{Instruction, State, SenseDir, SenseCondition, Marker, LR};
+use crate::number_theory::Random;
+use crate::zobrist::world_hash;
+
+// A map string with rocks around, anthills, food and a few rocks inside.
+// Kills are rare with the usual threshold, so it's lowered at times.
+#[derive(Clone, Debug)]
+struct SmallWorld(String, Rules);
+
+impl Arbitrary for SmallWorld {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let x = g.gen_range(3, 11);
+        let y = g.gen_range(3, 11);
+        let mut map = format!("{}\n{}\n", x, y);
+        for cy in 0..y {
+            let cells: Vec<String> = (0..x).map(|cx| {
+                if cx == 0 || cy == 0 || cx == x - 1 || cy == y - 1 {
+                    return "#".to_string();
+                }
+                match g.gen_range(0, 10) {
+                    0 => "#".to_string(),
+                    1 | 2 => "+".to_string(),
+                    3 | 4 => "-".to_string(),
+                    5 | 6 => g.gen_range(1, 10).to_string(),
+                    _ => ".".to_string(),
+                }
+            }).collect();
+            if cy % 2 == 1 {
+                map.push(' ');
+            }
+            map.push_str(&cells.join(" "));
+            map.push('\n');
+        }
+        SmallWorld(map, Rules { kill_threshold: g.gen_range(2, 6), ..Rules::default() })
+    }
+}
+
+// Any instruction table with valid transitions,
+// biased towards moving food around.
+#[derive(Clone, Debug)]
+struct SmallBrain(Vec<Instruction>);
+
+fn random_insn<G: Gen>(g: &mut G, num_states: u16) -> Instruction {
+    let mut st = || State(g.gen_range(0, num_states));
+    let (s1, s2) = (st(), st());
+    let marker = Marker::new(g.gen_range(0, 6));
+    match g.gen_range(0, 10) {
+        0 => {
+            let dir = [SenseDir::Here, SenseDir::Ahead, SenseDir::LeftAhead, SenseDir::RightAhead][g.gen_range(0, 4)];
+            let cond = [
+                SenseCondition::Friend, SenseCondition::Foe,
+                SenseCondition::FriendWithFood, SenseCondition::FoeWithFood,
+                SenseCondition::Food, SenseCondition::Rock,
+                SenseCondition::Marker(marker), SenseCondition::FoeMarker,
+                SenseCondition::Home, SenseCondition::FoeHome,
+            ][g.gen_range(0, 10)];
+            Instruction::Sense(dir, s1, s2, cond)
+        }
+        1 => Instruction::Mark(marker, s1),
+        2 => Instruction::Unmark(marker, s1),
+        3 | 4 => Instruction::PickUp(s1, s2),
+        5 => Instruction::Drop(s1),
+        6 => Instruction::Turn(if g.gen() { LR::Left } else { LR::Right }, s1),
+        7 | 8 => Instruction::Move(s1, s2),
+        _ => Instruction::Flip(g.gen_range(1, 5), s1, s2),
+    }
+}
+
+impl Arbitrary for SmallBrain {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let n = g.gen_range(1, 16);
+        SmallBrain((0..n).map(|_| random_insn(g, n)).collect())
+    }
+}
+
+// Food on the map, anthills included, plus food carried by ants.
+fn total_food(world: &World) -> usize {
+    world.data.values().map(|token| match token {
+        Clear(cont) => cont.food.0 as usize + cont.ant.as_ref().map_or(0, |ant| ant.has_food as usize),
+        _ => 0,
+    }).sum()
+}
+
+fn check_consistent(world: &World) -> Result<(), String> {
+    let mut ids = HashSet::new();
+    for (p, token) in &world.data {
+        if let Clear(cont) = token {
+            if let Some(ant) = &cont.ant {
+                if !ids.insert(ant.id) {
+                    return Err(format!("ant {} is on the map twice", ant.id));
+                }
+                if world.ant_positions.get(&ant.id) != Some(p) {
+                    return Err(format!("ant {} is at {:?}, ant_positions says {:?}",
+                        ant.id, p, world.ant_positions.get(&ant.id)));
+                }
+            }
+        }
+    }
+    if ids.len() != world.ant_positions.len() {
+        return Err(format!("{} ants on the map, {} in ant_positions", ids.len(), world.ant_positions.len()));
+    }
+    if world.hash != world_hash(world) {
+        return Err("stale hash".to_string());
+    }
+    Ok(())
+}
+
+fn play(map: SmallWorld, red: SmallBrain, black: SmallBrain, seed: u32) -> Result<(), String> {
+    let mut world = World::from_map_string(&map.0);
+    world.rules = map.1;
+    let mut other = world.clone();
+    let brains = [red.0, black.0];
+    let mut tables = brains.clone();
+    let mut rng = Random::new(seed);
+    let mut other_rng = Random::new(seed);
+
+    let ants = world.ant_positions.len();
+    let food = total_food(&world);
+    for round in 0..300 {
+        let before = world.ant_positions.len();
+        world.round(&brains, &mut rng);
+        let [red_table, black_table] = &mut tables;
+        other.round_with_minds([red_table, black_table], &mut other_rng, &mut ());
+
+        let context = |e| format!("round {}: {}\n{}", round, e, world);
+        check_consistent(&world).map_err(context)?;
+        if world.ant_positions.len() > before {
+            return Err(context(format!("{} ants after {}", world.ant_positions.len(), before)));
+        }
+        let dead = ants - world.ant_positions.len();
+        let expected = food + dead * world.rules.food_on_death as usize;
+        if total_food(&world) != expected {
+            return Err(context(format!("{} food, expected {}", total_food(&world), expected)));
+        }
+        if other.hash != world.hash {
+            return Err(context("round_with_minds disagrees".to_string()));
+        }
+    }
+    Ok(())
+}
+
+#[quickcheck]
+fn simulation_invariants(world: SmallWorld, red: SmallBrain, black: SmallBrain, seed: u32) -> Result<(), String> {
+    play(world, red, black, seed)
+}
+
+#[test]
+fn sample_brain_on_tiny_world() {
+    let world = SmallWorld(std::fs::read_to_string("data/tiny.world").unwrap(), Rules::default());
+    let brain = SmallBrain(crate::neurology::parse_ant(&std::fs::read_to_string("data/sample.ant").unwrap()));
+    assert_eq!(play(world, brain.clone(), brain, 42), Ok(()));
+}
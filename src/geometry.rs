@@ -2,6 +2,7 @@ use num_traits::FromPrimitive;
 use num_derive::FromPrimitive;
 
 use std::{fmt, collections::HashMap};
+use std::convert::TryFrom;
 
 use crate::prelude::{
     even,
@@ -119,15 +120,9 @@ pub fn sensed_cell(p : Pos, d : Dir, sd : SenseDir) -> Option<Pos> {
 }
 
 impl Pos {
-    pub fn distance(self, them : Pos) -> u8 {
-        // max(|dy|, |dx| + floor(|dy|/2) + offset)
-        // offset = ( (even(y1) && odd(y2) && (x1 < x2)) || (even(y2) && odd(y1) && (x2 < x1)) ) ? 1 : 0
-        let dx = i16::from(them.x - self.x).abs() as u8;
-        let dy = i16::from(them.y - self.y).abs() as u8;
-        let offset =
-            if (even(self.y) && !even(them.y) && self.x < them.x) ||
-               (even(them.y) && !even(self.y) && them.x < self.x) { 1 } else { 0 };
-        std::cmp::max(dy, dx + dy/2 + offset)
+    // Number of steps between the cells, rocks aside.
+    pub fn distance(self, them : Pos) -> u32 {
+        Hex::from(self).distance(Hex::from(them))
     }
 
     // assuming that the world is always 100x100, as per spec
@@ -163,13 +158,129 @@ impl std::fmt::Debug for Pos {
     }
 }
 
+// Component-wise, panics (in release builds too) if either coordinate
+// would go below 0. For steps between cells use Hex instead.
 impl std::ops::SubAssign for Pos {
     fn sub_assign(&mut self, rhs: Pos) {
-        self.x -= rhs.x;
-        self.y -= rhs.y;
+        match (self.x.checked_sub(rhs.x), self.y.checked_sub(rhs.y)) {
+            (Some(x), Some(y)) => *self = Pos{x, y},
+            _ => panic!("{:?} - {:?} is off the map", self, rhs),
+        }
+    }
+}
+
+// Axial coordinates, for arithmetic on cells.
+// Pos has odd rows shifted right, which makes adj() depend on the row,
+// here every Dir is the same step everywhere: E is (1, 0), SE is (0, 1).
+// The implied third cube coordinate is s = -q - r.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Hex {
+    pub q : i32,
+    pub r : i32,
+}
+
+impl From<Pos> for Hex {
+    fn from(Pos{x,y} : Pos) -> Hex {
+        let (x, y) = (i32::from(x), i32::from(y));
+        Hex{ q: x - (y - (y & 1)) / 2, r: y }
+    }
+}
+
+impl From<Dir> for Hex {
+    fn from(d : Dir) -> Hex {
+        match d {
+            Dir::E  => Hex{ q:  1, r:  0 },
+            Dir::SE => Hex{ q:  0, r:  1 },
+            Dir::SW => Hex{ q: -1, r:  1 },
+            Dir::W  => Hex{ q: -1, r:  0 },
+            Dir::NW => Hex{ q:  0, r: -1 },
+            Dir::NE => Hex{ q:  1, r: -1 },
+        }
+    }
+}
+
+impl std::ops::Add for Hex {
+    type Output = Hex;
+    fn add(self, rhs : Hex) -> Hex {
+        Hex{ q: self.q + rhs.q, r: self.r + rhs.r }
+    }
+}
+
+impl std::ops::Sub for Hex {
+    type Output = Hex;
+    fn sub(self, rhs : Hex) -> Hex {
+        Hex{ q: self.q - rhs.q, r: self.r - rhs.r }
+    }
+}
+
+impl std::ops::Mul<i32> for Hex {
+    type Output = Hex;
+    fn mul(self, k : i32) -> Hex {
+        Hex{ q: self.q * k, r: self.r * k }
+    }
+}
+
+impl Hex {
+    pub const ORIGIN : Hex = Hex{ q: 0, r: 0 };
+
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+
+    // None if it doesn't fit in a Pos, map bounds aren't checked.
+    pub fn to_pos(self) -> Option<Pos> {
+        let x = self.q + (self.r - (self.r & 1)) / 2;
+        match (u8::try_from(x), u8::try_from(self.r)) {
+            (Ok(x), Ok(y)) => Some(Pos{x, y}),
+            _ => None,
+        }
+    }
+
+    pub fn neighbour(self, d : Dir) -> Hex {
+        self + Hex::from(d)
+    }
+
+    pub fn distance(self, other : Hex) -> u32 {
+        let d = self - other;
+        (d.q.unsigned_abs() + d.r.unsigned_abs() + d.s().unsigned_abs()) / 2
+    }
+
+    // Clockwise around the origin, by as much as turning from E to d.
+    pub fn rotate(self, d : Dir) -> Hex {
+        let mut h = self;
+        for _ in 0..d as u8 {
+            h = Hex{ q: -h.r, r: -h.s() };
+        }
+        h
+    }
+
+    // Left and right swapped, about the vertical line through the origin.
+    pub fn mirror(self) -> Hex {
+        Hex{ q: -self.q - self.r, r: self.r }
     }
 }
 
+// Cells at exactly that distance, clockwise from the NW corner.
+pub fn ring(centre : Hex, radius : u32) -> impl Iterator<Item=Hex> {
+    let radius = radius as i32;
+    let sides = if radius == 0 { 0..1 } else { 0..6 };
+    sides.flat_map(move |i| {
+        let corner = centre + Hex::from(Dir::NW.cw(i)) * radius;
+        let side = Hex::from(Dir::E.cw(i));
+        (0..radius.max(1)).map(move |j| corner + side * j)
+    })
+}
+
+// Cells within that distance, nearest first.
+pub fn spiral(centre : Hex, radius : u32) -> impl Iterator<Item=Hex> {
+    (0..=radius).flat_map(move |k| ring(centre, k))
+}
+
+// ENTRY_POINT
+pub fn geometry_entry_point() {
+    println!("Hello from prelude")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -188,10 +299,98 @@ mod tests {
         assert_eq!(None, adj(Pos{x:0,y:0}, Dir::NW));
     }
 
-}
+    #[test]
+    fn hex_matches_adj() {
+        for x in 0..=255 {
+            for y in 0..=255 {
+                let p = Pos{x, y};
+                assert_eq!(Hex::from(p).to_pos(), Some(p));
+                if p.out_of_bounds() {
+                    continue;
+                }
+                for d in simple_enum_iter::<Dir>(6) {
+                    if let Some(a) = adj(p, d) {
+                        assert_eq!(Hex::from(p).neighbour(d), Hex::from(a));
+                    }
+                }
+            }
+        }
+        assert_eq!(Hex{ q: -1, r: 0 }.to_pos(), None);
+        assert_eq!(Hex{ q: 0, r: -1 }.to_pos(), None);
+        assert_eq!(Hex{ q: 200, r: 200 }.to_pos(), None);
+    }
+
+    #[test]
+    fn sub_assign() {
+        let mut p = Pos{x: 5, y: 3};
+        p -= Pos{x: 2, y: 3};
+        assert_eq!(p, Pos{x: 3, y: 0});
+    }
+
+    #[test]
+    #[should_panic(expected = "(0, 4) - (1, 2) is off the map")]
+    fn sub_assign_underflow() {
+        let mut p = Pos{x: 0, y: 4};
+        p -= Pos{x: 1, y: 2};
+    }
+
+    #[test]
+    fn distance_is_bfs_distance() {
+        const N : u8 = 20;
+        let cells : Vec<Pos> = (0..N).flat_map(|y| (0..N).map(move |x| Pos{x, y})).collect();
+        for &from in &cells {
+            let mut dist = HashMap::new();
+            dist.insert(from, 0);
+            let mut queue = std::collections::VecDeque::from(vec![from]);
+            while let Some(p) = queue.pop_front() {
+                for d in simple_enum_iter::<Dir>(6) {
+                    if let Some(a) = adj(p, d) {
+                        if a.x < N && a.y < N && !dist.contains_key(&a) {
+                            dist.insert(a, dist[&p] + 1);
+                            queue.push_back(a);
+                        }
+                    }
+                }
+            }
+            for &to in &cells {
+                assert_eq!(from.distance(to), dist[&to], "{:?} {:?}", from, to);
+            }
+        }
+        // no overflow far apart either way
+        assert_eq!(Pos{x: 255, y: 255}.distance(Pos{x: 0, y: 0}), 383);
+        assert_eq!(Pos{x: 0, y: 0}.distance(Pos{x: 255, y: 255}), 383);
+    }
+
+    #[test]
+    fn rings_and_rotations() {
+        let centre = Hex::from(Pos{x: 7, y: 3});
+        for radius in 0..6 {
+            let ring : Vec<Hex> = ring(centre, radius).collect();
+            assert_eq!(ring.len(), if radius == 0 { 1 } else { 6 * radius as usize });
+            assert!(ring.iter().all(|h| h.distance(centre) == radius));
+            for (i, h) in ring.iter().enumerate() {
+                assert!(ring[(i + 1) % ring.len()].distance(*h) <= 1);
+            }
+
+            let spiral : Vec<Hex> = spiral(centre, radius).collect();
+            let distinct : std::collections::HashSet<_> = spiral.iter().collect();
+            assert_eq!(distinct.len(), spiral.len());
+            assert_eq!(spiral.len() as u32, 1 + 3 * radius * (radius + 1));
+        }
+
+        for d in simple_enum_iter::<Dir>(6) {
+            assert_eq!(Hex::from(Dir::E).rotate(d), Hex::from(d));
+            assert_eq!(Hex::from(Dir::SW).rotate(d), Hex::from(Dir::SW.cw(d as u8)));
+            let h = Hex{ q: 3, r: -5 };
+            assert_eq!(h.rotate(d).distance(Hex::ORIGIN), h.distance(Hex::ORIGIN));
+            let mirrored = match d {
+                Dir::E => Dir::W, Dir::W => Dir::E,
+                Dir::SE => Dir::SW, Dir::SW => Dir::SE,
+                Dir::NE => Dir::NW, Dir::NW => Dir::NE,
+            };
+            assert_eq!(Hex::from(d).mirror(), Hex::from(mirrored));
+        }
+    }
 
-// ENTRY_POINT
-pub fn geometry_entry_point() {
-    println!("Hello from prelude")
 }
 
@@ -0,0 +1,100 @@
+// What happens during a game, as reported by World::step_observed().
+// Statistics, replays and the like can be built on top of this
+// instead of diffing worlds.
+
+use crate::biology::Color;
+use crate::geometry::Pos;
+use crate::phenomenology::Marker;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Moved { from: Pos },
+    Blocked,
+    PickedUp,
+    // Dropped food anywhere except on its own anthill.
+    Dropped,
+    // Dropped food on its own anthill.
+    FoodDeposited,
+    Marked(Marker),
+    Unmarked(Marker),
+    // Surrounded by enemies, pos is where it died.
+    Killed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub round: usize,
+    pub ant_id: u8,
+    pub color: Color,
+    pub pos: Pos,
+    pub kind: EventKind,
+}
+
+pub trait Observer {
+    fn observe(&mut self, event: Event);
+}
+
+// Ignores everything.
+impl Observer for () {
+    fn observe(&mut self, _event: Event) {}
+}
+
+// Records everything.
+impl Observer for Vec<Event> {
+    fn observe(&mut self, event: Event) {
+        self.push(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartography::World;
+    use crate::neurology::parse_ant;
+    use crate::number_theory::Random;
+
+    #[test]
+    fn replay_positions() {
+        let mut world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let brain = parse_ant(&std::fs::read_to_string("data/sample.ant").unwrap());
+        let brains = [brain.clone(), brain];
+        let mut rng = Random::new(42);
+
+        let mut positions = world.ant_positions.clone();
+        let mut events: Vec<Event> = vec![];
+        for _ in 0..1000 {
+            world.round_observed(&brains, &mut rng, &mut events);
+        }
+        assert_eq!(world.round, 1000);
+        assert!(events.iter().any(|e| e.kind == EventKind::Blocked));
+        assert!(events.iter().any(|e| e.kind == EventKind::PickedUp));
+
+        for e in &events {
+            match e.kind {
+                EventKind::Moved { from } => {
+                    assert_eq!(positions.insert(e.ant_id, e.pos), Some(from));
+                }
+                EventKind::Killed => {
+                    assert_eq!(positions.remove(&e.ant_id), Some(e.pos));
+                }
+                _ => assert_eq!(positions[&e.ant_id], e.pos),
+            }
+        }
+        assert_eq!(positions, world.ant_positions);
+    }
+
+    #[test]
+    fn markers_only_when_changed() {
+        let mut world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        world.rules.num_markers = 3;
+        // marks 1 twice, then 4 which is out of range, unmarks 1 twice, unmarks 2 which is clear
+        let brain = parse_ant("Mark 1 1\nMark 1 2\nMark 4 3\nUnmark 1 4\nUnmark 1 5\nUnmark 2 5\nTurn Left 5");
+        let mut rng = Random::new(42);
+        let mut events: Vec<Event> = vec![];
+        for _ in 0..7 {
+            world.step_observed(0, &mut rng, |ant, _| brain[ant.state.0 as usize], &mut events);
+        }
+        let kinds: Vec<&EventKind> = events.iter().map(|e| &e.kind).collect();
+        assert_eq!(kinds, [&EventKind::Marked(Marker(1)), &EventKind::Unmarked(Marker(1))]);
+    }
+}
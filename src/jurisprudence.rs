@@ -0,0 +1,101 @@
+// Game constants. The defaults are the ones from the spec,
+// the others are for checking how robust a brain is.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rules {
+    // Rounds an ant rests after moving.
+    pub rest_after_move: u8,
+    // That many adjacent enemies kill an ant.
+    pub kill_threshold: u8,
+    // Food left where an ant dies, on top of what it carried.
+    pub food_on_death: u16,
+    // Only markers below that can be set and sensed, at most 6.
+    pub num_markers: usize,
+    pub rounds: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            rest_after_move: 14,
+            kill_threshold: 5,
+            food_on_death: 3,
+            num_markers: 6,
+            rounds: 100000,
+        }
+    }
+}
+
+impl Rules {
+    // Default rules with some of them changed, like "rest=10,kill=4".
+    // Names are rest, kill, death_food, markers and rounds.
+    pub fn parse(s: &str) -> Result<Rules, String> {
+        let mut rules = Rules::default();
+        for item in s.split(',').filter(|item| !item.is_empty()) {
+            let (name, value) = item.split_once('=')
+                .ok_or_else(|| format!("expected name=value, got {:?}", item))?;
+            let bad_value = |_| format!("bad value for {}: {:?}", name, value);
+            match name {
+                "rest" => rules.rest_after_move = value.parse().map_err(bad_value)?,
+                "kill" => rules.kill_threshold = value.parse().map_err(bad_value)?,
+                "death_food" => rules.food_on_death = value.parse().map_err(bad_value)?,
+                "markers" => rules.num_markers = value.parse().map_err(bad_value)?,
+                "rounds" => rules.rounds = value.parse().map_err(bad_value)?,
+                _ => return Err(format!("unknown rule {:?}", name)),
+            }
+        }
+        // an ant has 6 neighbours, and with 0 any ant dies as soon as it is checked
+        if !(1..=6).contains(&rules.kill_threshold) {
+            return Err(format!("kill must be from 1 to 6, got {}", rules.kill_threshold));
+        }
+        if rules.num_markers > 6 {
+            return Err(format!("at most 6 markers, got {}", rules.num_markers));
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use crate::cartography::World;
+    use crate::neurology::parse_ant;
+    use crate::number_theory::Random;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Rules::parse(""), Ok(Rules::default()));
+        assert_eq!(Rules::parse("rest=0,markers=2"),
+            Ok(Rules { rest_after_move: 0, num_markers: 2, ..Rules::default() }));
+        assert_eq!(Rules::parse("kill=x"), Err(r#"bad value for kill: "x""#.to_string()));
+        assert_eq!(Rules::parse("kill=0"), Err("kill must be from 1 to 6, got 0".to_string()));
+        assert_eq!(Rules::parse("kill=7"), Err("kill must be from 1 to 6, got 7".to_string()));
+        assert_eq!(Rules::parse("kill=1"), Ok(Rules { kill_threshold: 1, ..Rules::default() }));
+        assert_eq!(Rules::parse("markers=7"), Err("at most 6 markers, got 7".to_string()));
+        assert_eq!(Rules::parse("speed=2"), Err(r#"unknown rule "speed""#.to_string()));
+    }
+
+    #[test]
+    fn rules_change_the_game() {
+        // ends up in state 2 if marker 3 could be set, 3 otherwise, then moves
+        let brain = parse_ant("Mark 3 1\nSense Here 2 3 Marker 3\nMove 2 2\nMove 3 3");
+        let play = |rules: Rules| {
+            let mut world = World::from_map_string(
+                &std::fs::read_to_string("data/tiny.world").unwrap());
+            world.rules = rules;
+            let mut rng = Random::new(42);
+            for _ in 0..3 {
+                world.round(&[brain.clone(), brain.clone()], &mut rng);
+            }
+            let ants: Vec<_> = world.ant_positions.values()
+                .map(|&pos| world.ant_at(pos).unwrap())
+                .collect();
+            let states: HashSet<u16> = ants.iter().map(|ant| ant.state.0).collect();
+            (states, ants.iter().map(|ant| ant.resting).max().unwrap())
+        };
+        assert_eq!(play(Rules::default()), (HashSet::from([2]), 14));
+        assert_eq!(play(Rules { num_markers: 3, rest_after_move: 5, ..Rules::default() }),
+            (HashSet::from([3]), 5));
+    }
+}
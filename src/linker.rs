@@ -0,0 +1,195 @@
+// Combines brain fragments into one brain.
+//
+// A fragment is a brain whose transitions may also point past its last
+// state: state len + k means "leave through exit k". The linker lays the
+// fragments out one after another, relocates their states and points
+// every exit to the entry of the fragment it is wired to.
+//
+//   let mut linker = Linker::new();
+//   linker.add("forage", Fragment::new(forage, &["got_food"]));
+//   linker.add("home", Fragment::new(home, &["dropped"]));
+//   linker.wire("forage", "got_food", "home", "start");
+//   linker.wire("home", "dropped", "forage", "start");
+//   let brain = linker.link("forage", "start")?;
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use crate::neurology::{Brain, State, MAX_STATES};
+
+pub struct Fragment {
+    pub brain: Brain,
+    // Named states to enter the fragment through, "start" is state 0.
+    pub entries: Vec<(String, usize)>,
+    // Exit k is the transition to state brain.states.len() + k.
+    pub exits: Vec<String>,
+}
+
+impl Fragment {
+    pub fn new(brain: impl Into<Brain>, exits: &[&str]) -> Self {
+        Fragment {
+            brain: brain.into(),
+            entries: vec![("start".to_string(), 0)],
+            exits: exits.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    pub fn add_entry(&mut self, name: &str, state: usize) {
+        self.entries.push((name.to_string(), state));
+    }
+
+    fn entry(&self, name: &str) -> Option<usize> {
+        self.entries.iter().find(|(n, _)| n == name).map(|&(_, st)| st)
+    }
+
+    fn exit_idx(&self, name: &str) -> Option<usize> {
+        self.exits.iter().position(|n| n == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct LinkError(pub String);
+
+pub struct Linker {
+    fragments: Vec<(String, Fragment)>,
+    // (fragment, exit) -> (fragment, entry)
+    wires: HashMap<(String, String), (String, String)>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Linker { fragments: vec![], wires: HashMap::new() }
+    }
+
+    pub fn add(&mut self, name: &str, fragment: Fragment) {
+        assert!(self.fragment_idx(name).is_none(), "duplicate fragment {:?}", name);
+        self.fragments.push((name.to_string(), fragment));
+    }
+
+    pub fn wire(&mut self, from: &str, exit: &str, to: &str, entry: &str) {
+        let old = self.wires.insert(
+            (from.to_string(), exit.to_string()),
+            (to.to_string(), entry.to_string()));
+        assert!(old.is_none(), "exit {}.{} is already wired", from, exit);
+    }
+
+    fn fragment_idx(&self, name: &str) -> Option<usize> {
+        self.fragments.iter().position(|(n, _)| n == name)
+    }
+
+    // Where (fragment, entry) ends up in the combined brain.
+    fn resolve(&self, offsets: &[usize], fragment: &str, entry: &str) -> Result<usize, LinkError> {
+        let idx = self.fragment_idx(fragment).ok_or_else(||
+            LinkError(format!("unknown fragment {:?}", fragment)))?;
+        let st = self.fragments[idx].1.entry(entry).ok_or_else(||
+            LinkError(format!("fragment {:?} has no entry {:?}", fragment, entry)))?;
+        Ok(offsets[idx] + st)
+    }
+
+    // The combined brain starts at the given entry. Comments are prefixed
+    // with the fragment name, and states no longer reachable are dropped.
+    pub fn link(&self, fragment: &str, entry: &str) -> Result<Brain, LinkError> {
+        let mut offsets = vec![];
+        let mut total = 0;
+        for (_, f) in &self.fragments {
+            offsets.push(total);
+            total += f.brain.states.len();
+        }
+        if total > usize::from(u16::MAX) {
+            return Err(LinkError(format!("{} states before dropping unreachable ones", total)));
+        }
+        for (from, exit) in self.wires.keys() {
+            let idx = self.fragment_idx(from).ok_or_else(||
+                LinkError(format!("unknown fragment {:?}", from)))?;
+            if self.fragments[idx].1.exit_idx(exit).is_none() {
+                return Err(LinkError(format!("fragment {:?} has no exit {:?}", from, exit)));
+            }
+        }
+
+        let mut brain = Brain::default();
+        for (idx, (name, f)) in self.fragments.iter().enumerate() {
+            for (entry_name, st) in &f.entries {
+                if *st >= f.brain.states.len() {
+                    return Err(LinkError(format!(
+                        "entry {}.{} is out of range", name, entry_name)));
+                }
+            }
+            let exits = f.exits.iter()
+                .map(|exit| match self.wires.get(&(name.clone(), exit.clone())) {
+                    Some((to, entry)) => self.resolve(&offsets, to, entry),
+                    None => Err(LinkError(format!("exit {}.{} is not wired", name, exit))),
+                })
+                .collect::<Result<Vec<usize>, LinkError>>()?;
+            for (i, st) in f.brain.states.iter().enumerate() {
+                let mut st = st.clone();
+                for s in st.insn.transitions_mut() {
+                    let s_idx = s.0 as usize;
+                    let target = if s_idx < f.brain.states.len() {
+                        offsets[idx] + s_idx
+                    } else {
+                        *exits.get(s_idx - f.brain.states.len()).ok_or_else(||
+                            LinkError(format!("state {} of {:?} refers to undeclared exit {}",
+                                i, name, s_idx - f.brain.states.len())))?
+                    };
+                    *s = State(target.try_into().unwrap());
+                }
+                st.comment = Some(match st.comment {
+                    Some(c) => format!("{}: {}", name, c),
+                    None => name.clone(),
+                });
+                brain.states.push(st);
+            }
+        }
+
+        let start = self.resolve(&offsets, fragment, entry)?;
+        let order: Vec<usize> = std::iter::once(start)
+            .chain((0..brain.states.len()).filter(|&i| i != start))
+            .collect();
+        let brain = brain.renumber(&order).remove_unreachable();
+        if brain.states.len() > MAX_STATES {
+            return Err(LinkError(format!(
+                "{} states, only {} are allowed", brain.states.len(), MAX_STATES)));
+        }
+        Ok(brain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neurology::parse_ant;
+
+    #[test]
+    fn link() {
+        // move until blocked, then leave
+        let forage = parse_ant("Move 0 1");
+        // turn twice and leave
+        let mut turn = Fragment::new(parse_ant("Turn Left 1\nTurn Left 2"), &["done"]);
+        turn.add_entry("half", 1);
+        let unused = Fragment::new(parse_ant("Drop 0"), &[]);
+
+        let mut linker = Linker::new();
+        linker.add("unused", unused);
+        linker.add("turn", turn);
+        linker.add("forage", Fragment::new(forage, &["blocked"]));
+        linker.wire("forage", "blocked", "turn", "half");
+        linker.wire("turn", "done", "forage", "start");
+        let brain = linker.link("forage", "start").unwrap();
+        assert_eq!(brain.insns(), parse_ant("Move 0 1\nTurn Left 0"));
+        assert_eq!(brain.states[1].comment.as_deref(), Some("turn"));
+
+        let brain = linker.link("turn", "start").unwrap();
+        assert_eq!(brain.insns(), parse_ant("Turn Left 1\nTurn Left 2\nMove 2 1"));
+
+        let err = |linker: &Linker| linker.link("forage", "start").err().unwrap().0;
+        let mut linker = Linker::new();
+        linker.add("forage", Fragment::new(parse_ant("Move 0 1"), &["blocked"]));
+        assert_eq!(err(&linker), "exit forage.blocked is not wired");
+        linker.wire("forage", "blocked", "home", "start");
+        assert_eq!(err(&linker), r#"unknown fragment "home""#);
+
+        let mut linker = Linker::new();
+        linker.add("forage", Fragment::new(parse_ant("Move 0 2"), &["blocked"]));
+        linker.wire("forage", "blocked", "forage", "start");
+        assert_eq!(err(&linker), r#"state 0 of "forage" refers to undeclared exit 1"#);
+    }
+}
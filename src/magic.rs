@@ -1,19 +1,18 @@
 use std::cell::RefCell;
 use std::fmt::Debug;
 use std::convert::TryInto;
-use std::collections::HashMap;
-use std::io::Write;
-use crate::{phenomenology::{Marker, SenseCondition}, neurology::{State, Instruction, SenseDir, LR}};
+use std::collections::{HashMap, HashSet};
+use crate::{phenomenology::{Marker, SenseCondition}, neurology::{State, Instruction, SenseDir, LR, Brain, BrainState}};
 
 #[macro_export]
 macro_rules! call {
     ($fn_name:ident($($arg:tt)*)) => {{
-        let loc = crate::magic::Loc {
+        let loc = $crate::magic::Loc {
             file: file!(),
             line: line!(),
             column: column!(),
         };
-        let _callret = crate::magic::CallRet::new(loc, stringify!($fn_name));
+        let _callret = $crate::magic::CallRet::new(loc, stringify!($fn_name));
         $fn_name($($arg)*)?
     }};
 }
@@ -21,16 +20,16 @@ macro_rules! call {
 #[macro_export]
 macro_rules! var {
     (let $name:ident = $value:expr) => {
-        let $name = crate::magic::Var::new(stringify!($name), $value);
+        let $name = $crate::magic::Var::new(stringify!($name), $value);
     };
     (let $name:ident: $tp:ty = $value:expr) => {
-        let $name: Var<$tp> = crate::magic::Var::new(stringify!($name), $value);
+        let $name: Var<$tp> = $crate::magic::Var::new(stringify!($name), $value);
     };
     (let mut $name:ident = $value:expr) => {
-        let mut $name = crate::magic::Var::new(stringify!($name), $value);
+        let mut $name = $crate::magic::Var::new(stringify!($name), $value);
     };
     (let mut $name:ident: $tp:ty = $value:expr) => {
-        let mut $name: Var<$tp> = crate::magic::Var::new(stringify!($name), $value);
+        let mut $name: Var<$tp> = $crate::magic::Var::new(stringify!($name), $value);
     };
 }
 
@@ -150,17 +149,23 @@ pub fn flip(p: u16) -> AntResult<bool> {
     })
 }
 
-pub struct AnnotatedBrain(Vec<(Instruction, String)>);
+pub struct AnnotatedBrain(pub Vec<(Instruction, String)>);
 
 impl AnnotatedBrain {
     pub fn save_to_file(&self, path: &str) {
-        let mut f = std::fs::File::create(path).unwrap();
-        let longest = self.0.iter().map(|(insn, _)| format!("{}", insn).len()).max().unwrap();
-        for (insn, comment) in &self.0 {
-            let s = format!("{}", insn);
-            writeln!(f, "{}{} ; {}",
-                s, " ".repeat(longest - s.len()), comment).unwrap();
-        }        
+        std::fs::write(path, Brain::from(self).dumps()).unwrap();
+    }
+}
+
+impl From<&AnnotatedBrain> for Brain {
+    fn from(brain: &AnnotatedBrain) -> Self {
+        Brain {
+            states: brain.0.iter().map(|(insn, comment)| BrainState {
+                comment: Some(comment.clone()),
+                ..BrainState::new(*insn)
+            }).collect(),
+            trailing: vec![],
+        }
     }
 }
 
@@ -177,6 +182,44 @@ impl std::fmt::Debug for AnnotatedBrain {
     }
 }
 
+#[derive(Default)]
+struct Params {
+    values: HashMap<&'static str, i32>,
+    read: HashSet<&'static str>,
+}
+
+thread_local!(static PARAMS: RefCell<Params> = RefCell::new(Params::default()));
+
+// A tunable constant of the ant, like loop bounds or flip odds.
+// It's the default unless compile_with_params() overrides it.
+pub fn param(name: &'static str, default: i32) -> i32 {
+    PARAMS.with(|params| {
+        let mut params = params.borrow_mut();
+        params.read.insert(name);
+        params.values.get(name).copied().unwrap_or(default)
+    })
+}
+
+// An ant with the values to try for its params, see sweep.rs.
+pub struct Template {
+    pub name: &'static str,
+    pub ant: fn() -> AntResult<()>,
+    pub params: &'static [(&'static str, &'static [i32])],
+}
+
+pub fn compile_with_params(ant: fn() -> AntResult<()>, values: &[(&'static str, i32)]) -> AnnotatedBrain {
+    PARAMS.with(|params| *params.borrow_mut() = Params {
+        values: values.iter().copied().collect(),
+        read: HashSet::new(),
+    });
+    let brain = compile(ant);
+    let params = PARAMS.with(|params| params.replace(Params::default()));
+    for (name, _) in values {
+        assert!(params.read.contains(name), "the ant never reads param {:?}", name);
+    }
+    brain
+}
+
 pub fn compile(ant: fn() -> AntResult<()>) -> AnnotatedBrain {
     let mut exe_to_state: HashMap<ExeState, State> = HashMap::new();
     let mut path_to_state: HashMap<Vec<(Instruction, Branch)>, usize> = HashMap::new();
@@ -77,10 +77,10 @@ fn looping_ant() -> AntResult<()> {
 fn spiral_ant() -> AntResult<()> {
     var!(let mut k = 1);
 
-    while k.get() <= 4 {
+    while k.get() <= param("max_k", 4) {
         call!(move_n(k.get()));
         call!(turn(LR::Left));
-        k.set(k.get() * 2);
+        k.set(k.get() * param("growth", 2));
     }
 
     loop {
@@ -88,6 +88,15 @@ fn spiral_ant() -> AntResult<()> {
     }
 }
 
+pub const SPIRAL_TEMPLATE: Template = Template {
+    name: "spiral",
+    ant: spiral_ant,
+    params: &[
+        ("max_k", &[2, 4, 8]),
+        ("growth", &[2, 3]),
+    ],
+};
+
 // ENTRY_POINT
 pub fn magic_example() {
     eprintln!("looping ant");
@@ -5,21 +5,36 @@ pub mod example_module;
 pub mod dev_server;
 pub mod prelude;
 pub mod geometry;
+pub mod topography;
 pub mod biology;
 pub mod geography;
 pub mod cartography;
 pub mod phenomenology;
 pub mod neurology;
+pub mod psychology;
+pub mod jurisprudence;
+pub mod history;
+pub mod zobrist;
 pub mod number_theory;
 pub mod vis_server;
 pub mod dump_trace;
 pub mod structured_compiler;
 pub mod magic_examples;
 pub mod tournament;
+pub mod linker;
+pub mod brain_diff;
+pub mod equivalence;
+pub mod evolution;
+pub mod sweep;
+pub mod fairness;
+pub mod symmetry;
 pub mod bouncing_ant;
 pub mod spanning_ant;
 pub mod py;
 pub mod py2ant;
+pub mod pydebug;
+#[cfg(test)]
+mod fuzz;
 
 // produced by build.rs
 include!(concat!(env!("OUT_DIR"), "/entry_points.rs"));
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::convert::TryInto;
 
 pub use crate::geometry::{
     SenseDir,
@@ -14,6 +15,9 @@ pub use crate::phenomenology::{
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct State(pub u16);
 
+// The number of states the game allows in an ant brain.
+pub const MAX_STATES: usize = 10000;
+
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -69,41 +73,56 @@ impl Instruction {
         std::iter::once(st1).chain(st2)
     }
 
+    // The instruction with all transitions zeroed.
+    pub fn shape(&self) -> Instruction {
+        let mut insn = *self;
+        for s in insn.transitions_mut() {
+            *s = State(0);
+        }
+        insn
+    }
+
     pub fn parse(s: &str) -> Self {
-        let end = s.find(';').unwrap_or_else(|| s.len());
+        Instruction::try_parse(s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    // Like parse(), but says what's wrong with s instead of panicking.
+    pub fn try_parse(s: &str) -> Result<Self, String> {
+        let end = s.find(';').unwrap_or(s.len());
         let s = &s[..end];
+        let err = |what: &str| format!("{} in instruction {:?}", what, s.trim());
         let mut it = s.split_whitespace().map(str::to_lowercase);
-        let cmd = it.next().unwrap();
+        let mut next = |what: &str| it.next().ok_or_else(|| err(&format!("missing {}", what)));
+        let cmd = next("command")?;
         let res = match cmd.as_str() {
             "sense" => {
-                let sense_dir = match it.next().unwrap().as_str() {
+                let sense_dir = match next("direction")?.as_str() {
                     "here" => SenseDir::Here,
                     "ahead" => SenseDir::Ahead,
                     "leftahead" => SenseDir::LeftAhead,
                     "rightahead" => SenseDir::RightAhead,
-                    _ => panic!("{:?}", s),
+                    t => return Err(err(&format!("unknown direction {:?}", t))),
                 };
-                let st1 = State(it.next().unwrap().parse().unwrap());
-                let st2 = State(it.next().unwrap().parse().unwrap());
-                let cond = match it.next().unwrap().as_str() {
+                let st1 = State(num(next("state")?, err)?);
+                let st2 = State(num(next("state")?, err)?);
+                let cond = match next("condition")?.as_str() {
                     "friend" => SenseCondition::Friend,
                     "foe" => SenseCondition::Foe,
                     "friendwithfood" => SenseCondition::FriendWithFood,
                     "foewithfood" => SenseCondition::FoeWithFood,
                     "food" => SenseCondition::Food,
                     "rock" => SenseCondition::Rock,
-                    "marker" => SenseCondition::Marker(
-                        Marker::new(it.next().unwrap().parse().unwrap())),
+                    "marker" => SenseCondition::Marker(parse_marker(next("marker")?, err)?),
                     "foemarker" => SenseCondition::FoeMarker,
                     "home" => SenseCondition::Home,
                     "foehome" => SenseCondition::FoeHome,
-                    _ => panic!("{:?}", s),
+                    t => return Err(err(&format!("unknown condition {:?}", t))),
                 };
                 Instruction::Sense(sense_dir, st1, st2, cond)
             }
             "mark" | "unmark" => {
-                let marker = Marker::new(it.next().unwrap().parse().unwrap());
-                let st = State(it.next().unwrap().parse().unwrap());
+                let marker = parse_marker(next("marker")?, err)?;
+                let st = State(num(next("state")?, err)?);
                 if cmd == "mark" {
                     Instruction::Mark(marker, st)
                 } else {
@@ -111,35 +130,48 @@ impl Instruction {
                 }
             }
             "pickup" => {
-                let st1 = State(it.next().unwrap().parse().unwrap());
-                let st2 = State(it.next().unwrap().parse().unwrap());
+                let st1 = State(num(next("state")?, err)?);
+                let st2 = State(num(next("state")?, err)?);
                 Instruction::PickUp(st1, st2)
             }
-            "drop" => Instruction::Drop(State(it.next().unwrap().parse().unwrap())),
+            "drop" => Instruction::Drop(State(num(next("state")?, err)?)),
             "turn" => {
-                let lr = match it.next().unwrap().as_str() {
+                let lr = match next("left or right")?.as_str() {
                     "left" => LR::Left,
                     "right" => LR::Right,
-                    _ => panic!("{:?}", s),
+                    t => return Err(err(&format!("expected left or right, got {:?}", t))),
                 };
-                let st = State(it.next().unwrap().parse().unwrap());
+                let st = State(num(next("state")?, err)?);
                 Instruction::Turn(lr, st)
             }
             "move" => {
-                let st1 = State(it.next().unwrap().parse().unwrap());
-                let st2 = State(it.next().unwrap().parse().unwrap());
+                let st1 = State(num(next("state")?, err)?);
+                let st2 = State(num(next("state")?, err)?);
                 Instruction::Move(st1, st2)
             }
             "flip" => {
-                let p = it.next().unwrap().parse().unwrap();
-                let st1 = State(it.next().unwrap().parse().unwrap());
-                let st2 = State(it.next().unwrap().parse().unwrap());
+                let p = num(next("probability")?, err)?;
+                let st1 = State(num(next("state")?, err)?);
+                let st2 = State(num(next("state")?, err)?);
                 Instruction::Flip(p, st1, st2)
             }
-            _ => panic!("{:?}", s),
+            t => return Err(err(&format!("unknown command {:?}", t))),
         };
-        assert!(it.next().is_none(), "{:?}", s);
-        res
+        if let Some(t) = it.next() {
+            return Err(err(&format!("unexpected {:?}", t)));
+        }
+        Ok(res)
+    }
+}
+
+fn num<T: std::str::FromStr>(t: String, err: impl Fn(&str) -> String) -> Result<T, String> {
+    t.parse().map_err(|_| err(&format!("bad number {:?}", t)))
+}
+
+fn parse_marker(t: String, err: impl Fn(&str) -> String) -> Result<Marker, String> {
+    match t.parse() {
+        Ok(i) if i < 6 => Ok(Marker::new(i)),
+        _ => Err(err(&format!("bad marker {:?}", t))),
     }
 }
 
@@ -175,6 +207,147 @@ pub fn dumps(insns: &[Instruction]) -> String {
 }
 
 
+// A state of a Brain together with its annotations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BrainState {
+    pub insn: Instruction,
+    // Whatever follows ';' on the instruction line, trimmed.
+    pub comment: Option<String>,
+    // Blank and comment-only lines right above the instruction, verbatim.
+    pub leading: Vec<String>,
+    // Line number (1-based) in the file the state was parsed from,
+    // None for states that were added later.
+    pub line: Option<usize>,
+}
+
+impl BrainState {
+    pub fn new(insn: Instruction) -> Self {
+        BrainState { insn, comment: None, leading: vec![], line: None }
+    }
+}
+
+impl From<Vec<Instruction>> for Brain {
+    fn from(insns: Vec<Instruction>) -> Self {
+        Brain::from_insns(&insns)
+    }
+}
+
+// Unlike parse_ant() and dumps(), keeps the comments,
+// so a brain can be loaded, transformed and saved again
+// with the annotations still next to the states they describe.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Brain {
+    pub states: Vec<BrainState>,
+    // Blank and comment-only lines after the last instruction.
+    pub trailing: Vec<String>,
+}
+
+impl Brain {
+    pub fn parse(s: &str) -> Self {
+        let mut states = vec![];
+        let mut pending = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let (code, comment) = match line.find(';') {
+                Some(pos) => (&line[..pos], Some(line[pos + 1..].trim().to_string())),
+                None => (line, None),
+            };
+            if code.trim().is_empty() {
+                pending.push(line.to_string());
+                continue;
+            }
+            states.push(BrainState {
+                insn: Instruction::parse(code),
+                comment,
+                leading: std::mem::take(&mut pending),
+                line: Some(i + 1),
+            });
+        }
+        Brain { states, trailing: pending }
+    }
+
+    pub fn from_insns(insns: &[Instruction]) -> Self {
+        Brain {
+            states: insns.iter().copied().map(BrainState::new).collect(),
+            trailing: vec![],
+        }
+    }
+
+    pub fn insns(&self) -> Vec<Instruction> {
+        self.states.iter().map(|st| st.insn).collect()
+    }
+
+    // Comments are aligned like in AnnotatedBrain::save_to_file().
+    pub fn dumps(&self) -> String {
+        let insns: Vec<String> = self.states.iter().map(|st| st.insn.to_string()).collect();
+        let longest = insns.iter().map(String::len).max().unwrap_or(0);
+        let mut res = String::new();
+        for (st, insn) in self.states.iter().zip(&insns) {
+            for line in &st.leading {
+                res.push_str(line);
+                res.push('\n');
+            }
+            res.push_str(insn);
+            if let Some(comment) = &st.comment {
+                res.push_str(&" ".repeat(longest - insn.len()));
+                res.push_str(" ; ");
+                res.push_str(comment);
+            }
+            res.push('\n');
+        }
+        for line in &self.trailing {
+            res.push_str(line);
+            res.push('\n');
+        }
+        res
+    }
+
+    // States reachable from state 0, in BFS order, none for an empty brain.
+    pub fn reachable(&self) -> Vec<usize> {
+        if self.states.is_empty() {
+            return vec![];
+        }
+        let mut order = vec![0];
+        let mut seen = vec![false; self.states.len()];
+        seen[0] = true;
+        let mut i = 0;
+        while i < order.len() {
+            for s in self.states[order[i]].insn.transitions() {
+                let s = s.0 as usize;
+                if !seen[s] {
+                    seen[s] = true;
+                    order.push(s);
+                }
+            }
+            i += 1;
+        }
+        order
+    }
+
+    // New state i is the old state order[i], transitions are patched accordingly.
+    // States left out of the order must not be referenced by the ones kept.
+    pub fn renumber(&self, order: &[usize]) -> Brain {
+        let mut new_idx = vec![None; self.states.len()];
+        for (i, &old) in order.iter().enumerate() {
+            assert!(new_idx[old].is_none(), "state {} listed twice", old);
+            new_idx[old] = Some(State(i.try_into().unwrap()));
+        }
+        let states = order.iter().map(|&old| {
+            let mut st = self.states[old].clone();
+            for s in st.insn.transitions_mut() {
+                *s = new_idx[s.0 as usize].unwrap_or_else(||
+                    panic!("state {} refers to dropped state {}", old, s.0));
+            }
+            st
+        }).collect();
+        Brain { states, trailing: self.trailing.clone() }
+    }
+
+    pub fn remove_unreachable(&self) -> Brain {
+        self.renumber(&self.reachable())
+    }
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -183,7 +356,16 @@ mod tests {
     fn parse_insn_test() {
         assert_eq!(
             Instruction::parse(" droP  42  ; zzz"),
-            Instruction::Drop(State(42)))
+            Instruction::Drop(State(42)));
+        assert_eq!(
+            Instruction::try_parse("Mark 6 1"),
+            Err("bad marker \"6\" in instruction \"Mark 6 1\"".to_string()));
+        assert_eq!(
+            Instruction::try_parse("move 1"),
+            Err("missing state in instruction \"move 1\"".to_string()));
+        assert_eq!(
+            Instruction::try_parse("drop 1 2"),
+            Err("unexpected \"2\" in instruction \"drop 1 2\"".to_string()));
     }
 
     #[test]
@@ -202,6 +384,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn brain_keeps_comments() {
+        let s = "\
+; a comment
+Move 2 2 ; go
+Turn Left 0
+
+; dead end
+Drop 3
+Flip 3 0 2   ; coin
+; the end
+";
+        let brain = Brain::parse(s);
+        assert_eq!(brain.states.len(), 4);
+        assert_eq!(brain.states[0].comment.as_deref(), Some("go"));
+        assert_eq!(brain.states[0].leading, ["; a comment"]);
+        assert_eq!(brain.states[2].leading, ["", "; dead end"]);
+        assert_eq!(brain.states[3].line, Some(7));
+        assert_eq!(brain.trailing, ["; the end"]);
+        assert_eq!(brain.dumps(), "\
+; a comment
+Move 2 2    ; go
+Turn Left 0
+
+; dead end
+Drop 3
+Flip 3 0 2  ; coin
+; the end
+");
+        assert_eq!(Brain::parse(&brain.dumps()), brain);
+
+        let brain = brain.remove_unreachable();
+        assert_eq!(brain.insns(), parse_ant("Move 1 1\nDrop 2\nFlip 3 0 1"));
+        assert_eq!(brain.states[1].leading, ["", "; dead end"]);
+        assert_eq!(brain.states[2].comment.as_deref(), Some("coin"));
+        assert_eq!(brain.states[2].line, Some(7));
+
+        let empty = Brain::parse("");
+        assert!(empty.reachable().is_empty());
+        assert_eq!(empty.remove_unreachable(), empty);
+    }
+
     #[test]
     fn dumps_roundtrip_test() {
         let s = std::fs::read_to_string("data/sample.ant").unwrap();
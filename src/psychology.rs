@@ -0,0 +1,86 @@
+// Minds: brains as code rather than instruction tables.
+//
+// A Mind picks the instruction for an ant each time it's the ant's turn.
+// The instruction's transitions say which state the ant ends up in, so
+// a native mind can keep using ant.state as its memory, and its own
+// fields for anything bigger. Senses let prototypes look around for free,
+// which the state machine ISA can't do, so don't read too much into
+// their scores.
+
+use crate::biology::Ant;
+use crate::cartography::World;
+use crate::geometry::{Pos, sensed_cell};
+use crate::neurology::{Instruction, SenseDir, SenseCondition};
+
+pub trait Mind {
+    fn decide(&mut self, ant: &Ant, senses: &Senses) -> Instruction;
+}
+
+pub struct Senses<'a> {
+    pub world: &'a World,
+    pub pos: Pos,
+    pub ant: &'a Ant,
+}
+
+impl Senses<'_> {
+    // Like the Sense instruction, false outside the map.
+    pub fn sense(&self, dir: SenseDir, cond: SenseCondition) -> bool {
+        match sensed_cell(self.pos, self.ant.direction, dir) {
+            Some(p) => self.world.cell_matches(p, cond, self.ant.color),
+            None => false,
+        }
+    }
+}
+
+// The usual instruction table, indexed by ant state.
+impl Mind for [Instruction] {
+    fn decide(&mut self, ant: &Ant, _senses: &Senses) -> Instruction {
+        self[ant.state.0 as usize]
+    }
+}
+
+impl Mind for Vec<Instruction> {
+    fn decide(&mut self, ant: &Ant, senses: &Senses) -> Instruction {
+        self[..].decide(ant, senses)
+    }
+}
+
+impl<F: FnMut(&Ant, &Senses) -> Instruction> Mind for F {
+    fn decide(&mut self, ant: &Ant, senses: &Senses) -> Instruction {
+        self(ant, senses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::neurology::{State, parse_ant, LR};
+    use crate::number_theory::Random;
+
+    #[test]
+    fn native_brain_plays_like_its_table() {
+        let world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let mut black = parse_ant(&std::fs::read_to_string("data/sample.ant").unwrap());
+
+        // state 0: move, state 1: turn after bumping into something
+        let mut table = parse_ant("Move 0 1\nTurn Left 0");
+        let mut native = |ant: &Ant, senses: &Senses| {
+            assert!(!senses.sense(SenseDir::Here, SenseCondition::Rock));
+            match ant.state.0 {
+                0 => Instruction::Move(State(0), State(1)),
+                _ => Instruction::Turn(LR::Left, State(0)),
+            }
+        };
+
+        let mut w1 = world.clone();
+        let mut rng1 = Random::new(42);
+        let mut w2 = world;
+        let mut rng2 = Random::new(42);
+        for _ in 0..300 {
+            w1.round_with_minds([&mut table, &mut black.clone()], &mut rng1, &mut ());
+            w2.round_with_minds([&mut native, &mut black], &mut rng2, &mut ());
+        }
+        assert_eq!(w1.ant_positions, w2.ant_positions);
+        assert_eq!(format!("{}", w1), format!("{}", w2));
+    }
+}
@@ -0,0 +1,90 @@
+// Ant instructions as builtins, like sense(HERE, FOOD) or mark(0).
+// The instruction goes to state 1 on success and to state 0 otherwise,
+// and the VM gets the state as input. User functions with the same
+// name take precedence, so older helpers like def move() keep working.
+
+use crate::neurology::{Instruction, State, SenseDir, LR, SenseCondition, Marker};
+use super::vm::Value;
+
+// name, number of arguments, whether it returns success (otherwise None)
+const BUILTINS: &[(&str, usize, bool)] = &[
+    ("sense", 2, true),
+    ("sense_marker", 2, true),
+    ("mark", 1, false),
+    ("unmark", 1, false),
+    ("pick_up", 0, true),
+    ("drop", 0, false),
+    ("turn", 1, false),
+    ("move", 0, true),
+    ("flip", 1, true),
+];
+
+const SENSE_DIRS: &[(&str, SenseDir)] = &[
+    ("here", SenseDir::Here),
+    ("ahead", SenseDir::Ahead),
+    ("left_ahead", SenseDir::LeftAhead),
+    ("right_ahead", SenseDir::RightAhead),
+];
+
+const SENSE_CONDITIONS: &[(&str, SenseCondition)] = &[
+    ("friend", SenseCondition::Friend),
+    ("foe", SenseCondition::Foe),
+    ("friendwithfood", SenseCondition::FriendWithFood),
+    ("foewithfood", SenseCondition::FoeWithFood),
+    ("food", SenseCondition::Food),
+    ("rock", SenseCondition::Rock),
+    ("foemarker", SenseCondition::FoeMarker),
+    ("home", SenseCondition::Home),
+    ("foehome", SenseCondition::FoeHome),
+];
+
+// Returns the static name, number of arguments and whether it returns success.
+pub fn builtin(name: &str) -> Option<(&'static str, usize, bool)> {
+    BUILTINS.iter().copied().find(|&(n, _, _)| n == name)
+}
+
+// On error returns the index of the bad argument and the message.
+pub fn make_insn(name: &str, args: &[Value]) -> Result<Instruction, (usize, String)> {
+    let (yes, no) = (State(1), State(0));
+    Ok(match name {
+        "sense" => Instruction::Sense(
+            lookup(SENSE_DIRS, &args[0], "sense direction").map_err(|e| (0, e))?,
+            yes, no,
+            lookup(SENSE_CONDITIONS, &args[1], "sense condition").map_err(|e| (1, e))?),
+        "sense_marker" => Instruction::Sense(
+            lookup(SENSE_DIRS, &args[0], "sense direction").map_err(|e| (0, e))?,
+            yes, no,
+            SenseCondition::Marker(marker(&args[1]).map_err(|e| (1, e))?)),
+        "mark" => Instruction::Mark(marker(&args[0]).map_err(|e| (0, e))?, no),
+        "unmark" => Instruction::Unmark(marker(&args[0]).map_err(|e| (0, e))?, no),
+        "pick_up" => Instruction::PickUp(yes, no),
+        "drop" => Instruction::Drop(no),
+        "turn" => Instruction::Turn(
+            lookup(&[("left", LR::Left), ("right", LR::Right)], &args[0], "turn direction")
+                .map_err(|e| (0, e))?,
+            no),
+        "move" => Instruction::Move(yes, no),
+        "flip" => match args[0] {
+            Value::Int(n) if n > 0 && n <= i32::from(u16::MAX) => Instruction::Flip(n as u16, yes, no),
+            _ => return Err((0, format!("flip() argument must be a positive integer, got {:?}", args[0]))),
+        }
+        _ => unreachable!("{:?} is not in BUILTINS", name),
+    })
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], v: &Value, what: &str) -> Result<T, String> {
+    if let Value::String(s) = v {
+        if let Some(&(_, t)) = table.iter().find(|(name, _)| name == s) {
+            return Ok(t);
+        }
+    }
+    let names: Vec<String> = table.iter().map(|(name, _)| format!("{:?}", name)).collect();
+    Err(format!("unknown {} {:?}, expected one of {}", what, v, names.join(", ")))
+}
+
+fn marker(v: &Value) -> Result<Marker, String> {
+    match *v {
+        Value::Int(i) if (0..6).contains(&i) => Ok(Marker::new(i as usize)),
+        _ => Err(format!("marker must be an integer from 0 to 5, got {:?}", v)),
+    }
+}
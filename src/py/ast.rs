@@ -17,10 +17,17 @@ impl Span {
 
 #[derive(Debug)]
 pub enum Expr {
-    Const(Value),
-    Var(String),
+    Const {
+        value: Value,
+        token_idx: usize,
+    },
+    Var {
+        name: String,
+        token_idx: usize,
+    },
     UnaryOp {
         op: &'static str,
+        op_token_idx: usize,
         arg: Box<Expr>,
     },
     BinaryOp {
@@ -34,14 +41,55 @@ pub enum Expr {
         open_paren_token_idx: usize,
         args: Vec<Expr>,
     },
+    IfExp {
+        cond: Box<Expr>,
+        then: Box<Expr>,
+        els: Box<Expr>,
+    },
+    // token_idx is the open paren, or the first token if there is none
+    Tuple {
+        items: Vec<Expr>,
+        token_idx: usize,
+    },
+    List {
+        items: Vec<Expr>,
+        open_bracket_token_idx: usize,
+    },
+    Index {
+        value: Box<Expr>,
+        open_bracket_token_idx: usize,
+        index: Box<Expr>,
+    },
+    // module.name
+    Attr {
+        value: Box<Expr>,
+        dot_token_idx: usize,
+        name: String,
+    },
 }
 
 impl Expr {
+    // The token to blame when evaluating this expression fails.
+    pub fn token_idx(&self) -> Option<usize> {
+        match self {
+            Expr::Const { token_idx, .. } |
+            Expr::Var { token_idx, .. } |
+            Expr::Tuple { token_idx, .. } => Some(*token_idx),
+            Expr::UnaryOp { op_token_idx, .. } |
+            Expr::BinaryOp { op_token_idx, .. } => Some(*op_token_idx),
+            Expr::Call { open_paren_token_idx, .. } => Some(*open_paren_token_idx),
+            Expr::List { open_bracket_token_idx, .. } |
+            Expr::Index { open_bracket_token_idx, .. } => Some(*open_bracket_token_idx),
+            Expr::Attr { dot_token_idx, .. } => Some(*dot_token_idx),
+            Expr::IfExp { .. } => None,
+        }
+    }
+
     fn to_sexpr(&self) -> String {
         match self {
-            Expr::Const(c) => format!("{:?}", c),
-            Expr::Var(v) => v.to_string(),
-            Expr::UnaryOp { op, arg } => format!("({} {})", op, arg.to_sexpr()),
+            Expr::Const { value, .. } => format!("{:?}", value),
+            Expr::Var { name, .. } => name.to_string(),
+            Expr::UnaryOp { op, arg, .. } => format!("({} {})", op, arg.to_sexpr()),
             Expr::BinaryOp { op, left, right, .. } => format!(
                 "({} {} {})",
                 op, left.to_sexpr(), right.to_sexpr()),
@@ -54,6 +102,25 @@ impl Expr {
                 res.push(')');
                 res
             }
+            Expr::IfExp { cond, then, els } => format!(
+                "(ifexp {} {} {})",
+                cond.to_sexpr(), then.to_sexpr(), els.to_sexpr()),
+            Expr::Tuple { items, .. } | Expr::List { items, .. } => {
+                let mut res = match self {
+                    Expr::Tuple { .. } => "(tuple".to_string(),
+                    _ => "(list".to_string(),
+                };
+                for item in items {
+                    res.push(' ');
+                    res += &item.to_sexpr();
+                }
+                res.push(')');
+                res
+            }
+            Expr::Index { value, index, .. } =>
+                format!("(index {} {})", value.to_sexpr(), index.to_sexpr()),
+            Expr::Attr { value, name, .. } =>
+                format!("(. {} {})", value.to_sexpr(), name),
         }
     }
 }
@@ -71,10 +138,17 @@ pub enum Stmt {
     },
     Expr(Expr),
     Assign {
+        token_idx: usize,
         left: String,
         right: Expr,
         op: Option<&'static str>,
     },
+    // a, b = right
+    Unpack {
+        token_idx: usize,
+        targets: Vec<String>,
+        right: Expr,
+    },
     If {
         cond: Expr,
         then: Block,
@@ -84,6 +158,15 @@ pub enum Stmt {
         cond: Expr,
         body: Block,
     },
+    // for var in range(start, stop, step)
+    For {
+        token_idx: usize,
+        var: String,
+        start: Expr,
+        stop: Expr,
+        step: Expr,
+        body: Block,
+    },
     Def {
         token_idx: usize,
         name: String,
@@ -94,6 +177,16 @@ pub enum Stmt {
         token_idx: usize,
         vars: Vec<String>,
     },
+    Import {
+        token_idx: usize,
+        module: String,
+    },
+    // from module import names
+    FromImport {
+        token_idx: usize,
+        module: String,
+        names: Vec<String>,
+    },
 }
 
 impl Stmt {
@@ -105,8 +198,10 @@ impl Stmt {
             Stmt::Return { expr: None, .. } => "return".to_string(),
             Stmt::Return { expr: Some(e), .. } => format!("(return {})", e.to_sexpr()),
             Stmt::Expr(e) => e.to_sexpr(),
-            Stmt::Assign { left, right, op } =>
+            Stmt::Assign { left, right, op, .. } =>
                 format!("({}= {} {})", op.unwrap_or(""), left, right.to_sexpr()),
+            Stmt::Unpack { targets, right, .. } =>
+                format!("(= ({}) {})", targets.join(" "), right.to_sexpr()),
             Stmt::If { cond, then, els } => {
                 let mut res = "(if ".to_string();
                 res.push_str(&cond.to_sexpr());
@@ -121,6 +216,10 @@ impl Stmt {
             }
             Stmt::While { cond, body } =>
                 format!("(while {} {})", cond.to_sexpr(), block_to_sexpr(body)),
+            Stmt::For { var, start, stop, step, body, .. } =>
+                format!("(for {} {} {} {} {})",
+                    var, start.to_sexpr(), stop.to_sexpr(), step.to_sexpr(),
+                    block_to_sexpr(body)),
             Stmt::Def { name, args, body, .. } => {
                 let mut res = "(def ".to_string();
                 res += name;
@@ -145,6 +244,9 @@ impl Stmt {
                 res.push(')');
                 res
             }
+            Stmt::Import { module, .. } => format!("(import {})", module),
+            Stmt::FromImport { module, names, .. } =>
+                format!("(from {} import {})", module, names.join(" ")),
         }
     }
 }
@@ -252,7 +354,7 @@ impl<'a> Parser<'a> {
                         expr = None;
                         self.consume();
                     } else {
-                        expr = Some(Box::new(self.parse_expr()?));
+                        expr = Some(Box::new(self.parse_expr_list()?));
                         self.consume_expected_kind(TokenKind::Newline)?;
                     }
                     block.push(Stmt::Return {
@@ -262,28 +364,7 @@ impl<'a> Parser<'a> {
                 }
                 (TokenKind::Keyword, "if") => {
                     self.consume();
-                    let cond = self.parse_expr()?;
-                    self.consume_expected(TokenKind::Punct, ":")?;
-                    self.consume_expected_kind(TokenKind::Newline)?;
-                    self.consume_expected_kind(TokenKind::Indent)?;
-                    let then = self.parse_block()?;
-                    self.consume_expected_kind(TokenKind::Dedent)?;
-
-                    let els;
-                    if self.peek_token() == (TokenKind::Keyword, "else") {
-                        self.consume();
-                        self.consume_expected(TokenKind::Punct, ":")?;
-                        self.consume_expected_kind(TokenKind::Newline)?;
-                        self.consume_expected_kind(TokenKind::Indent)?;
-                        els = Some(self.parse_block()?);
-                        self.consume_expected_kind(TokenKind::Dedent)?;
-                    } else {
-                        els = None;
-                    };
-                    
-                    block.push(Stmt::If {
-                        cond, then, els
-                    });
+                    block.push(self.parse_if_rest()?);
                 }
                 (TokenKind::Keyword, "while") => {
                     self.consume();
@@ -298,8 +379,11 @@ impl<'a> Parser<'a> {
                         body
                     });
                 }
+                (TokenKind::Keyword, "for") => block.push(self.parse_for()?),
                 (TokenKind::Keyword, "def") => block.push(self.parse_def()?),
                 (TokenKind::Keyword, "global") => block.push(self.parse_global()?),
+                (TokenKind::Keyword, "import") | (TokenKind::Keyword, "from") =>
+                    block.push(self.parse_import()?),
                 _ => {
                     block.push(self.parse_assign_or_expr()?);
                     self.consume_expected_kind(TokenKind::Newline)?;
@@ -309,6 +393,92 @@ impl<'a> Parser<'a> {
         Ok(block)
     }
 
+    // Parses the part of an 'if' statement after the 'if' or 'elif' keyword.
+    // 'elif' becomes a nested 'if' in the else branch.
+    fn parse_if_rest(&mut self) -> Result<Stmt, ParseError> {
+        let cond = self.parse_expr()?;
+        self.consume_expected(TokenKind::Punct, ":")?;
+        self.consume_expected_kind(TokenKind::Newline)?;
+        self.consume_expected_kind(TokenKind::Indent)?;
+        let then = self.parse_block()?;
+        self.consume_expected_kind(TokenKind::Dedent)?;
+
+        let els = match self.peek_token() {
+            (TokenKind::Keyword, "elif") => {
+                self.consume();
+                Some(vec![self.parse_if_rest()?])
+            }
+            (TokenKind::Keyword, "else") => {
+                self.consume();
+                self.consume_expected(TokenKind::Punct, ":")?;
+                self.consume_expected_kind(TokenKind::Newline)?;
+                self.consume_expected_kind(TokenKind::Indent)?;
+                let els = self.parse_block()?;
+                self.consume_expected_kind(TokenKind::Dedent)?;
+                Some(els)
+            }
+            _ => None,
+        };
+
+        Ok(Stmt::If { cond, then, els })
+    }
+
+    fn parse_for(&mut self) -> Result<Stmt, ParseError> {
+        let token_idx = self.peek_token_idx();
+        self.consume_expected(TokenKind::Keyword, "for")?;
+
+        let var = if let (TokenKind::Iden, name) = self.peek_token() {
+            name.to_string()
+        } else {
+            return Err(ParseError {
+                msg: "expected loop variable name".to_string(),
+                token_idx: self.peek_token_idx(),
+            });
+        };
+        self.consume();
+        self.consume_expected(TokenKind::Keyword, "in")?;
+
+        let range_token_idx = self.peek_token_idx();
+        let mut args = match self.parse_expr()? {
+            Expr::Call { f, args, .. } if matches!(&*f, Expr::Var { name, .. } if name == "range") => args,
+            _ => return Err(ParseError {
+                msg: "only 'for ... in range(...)' loops are supported".to_string(),
+                token_idx: range_token_idx,
+            }),
+        };
+        let int = |i| Expr::Const { value: Value::Int(i), token_idx: range_token_idx };
+        let (start, stop, step) = match args.len() {
+            1 => (int(0), args.pop().unwrap(), int(1)),
+            2 => {
+                let stop = args.pop().unwrap();
+                (args.pop().unwrap(), stop, int(1))
+            }
+            3 => {
+                let step = args.pop().unwrap();
+                let stop = args.pop().unwrap();
+                (args.pop().unwrap(), stop, step)
+            }
+            _ => return Err(ParseError {
+                msg: format!("range() takes 1 to 3 arguments ({} given)", args.len()),
+                token_idx: range_token_idx,
+            }),
+        };
+
+        self.consume_expected(TokenKind::Punct, ":")?;
+        self.consume_expected_kind(TokenKind::Newline)?;
+        self.consume_expected_kind(TokenKind::Indent)?;
+        let body = self.parse_block()?;
+        self.consume_expected_kind(TokenKind::Dedent)?;
+        Ok(Stmt::For {
+            token_idx,
+            var,
+            start,
+            stop,
+            step,
+            body,
+        })
+    }
+
     fn parse_global(&mut self) -> Result<Stmt, ParseError> {
         let token_idx = self.peek_token_idx();
         self.consume_expected(TokenKind::Keyword, "global")?;
@@ -345,6 +515,53 @@ impl<'a> Parser<'a> {
         })
     }
 
+    // import module
+    // from module import name1, name2
+    fn parse_import(&mut self) -> Result<Stmt, ParseError> {
+        let token_idx = self.peek_token_idx();
+        let is_from = self.peek_token() == (TokenKind::Keyword, "from");
+        self.consume();
+
+        let module = self.parse_name("expected module name")?;
+        if !is_from {
+            self.consume_expected_kind(TokenKind::Newline)?;
+            return Ok(Stmt::Import { token_idx, module });
+        }
+
+        self.consume_expected(TokenKind::Keyword, "import")?;
+        let mut names = vec![];
+        loop {
+            names.push(self.parse_name("expected name to import")?);
+            match self.peek_token() {
+                (TokenKind::Newline, _) => {
+                    self.consume();
+                    break;
+                }
+                (TokenKind::Punct, ",") => {
+                    self.consume();
+                }
+                _ => return Err(ParseError {
+                    msg: "expected newline or ','".to_string(),
+                    token_idx: self.peek_token_idx(),
+                })
+            }
+        }
+        Ok(Stmt::FromImport { token_idx, module, names })
+    }
+
+    fn parse_name(&mut self, msg: &str) -> Result<String, ParseError> {
+        if let (TokenKind::Iden, name) = self.peek_token() {
+            let name = name.to_string();
+            self.consume();
+            Ok(name)
+        } else {
+            Err(ParseError {
+                msg: msg.to_string(),
+                token_idx: self.peek_token_idx(),
+            })
+        }
+    }
+
     fn parse_def(&mut self) -> Result<Stmt, ParseError> {
         let token_idx = self.peek_token_idx();
         self.consume_expected(TokenKind::Keyword, "def").unwrap();
@@ -405,7 +622,8 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_assign_or_expr(&mut self) -> Result<Stmt, ParseError> {
-        let left = self.parse_expr()?;
+        let left = self.parse_expr_list()?;
+        let token_idx = self.peek_token_idx();
         let op = match self.peek_token() {
             (TokenKind::Punct, "=") => None,
             (TokenKind::Punct, "+=") => Some("+"),
@@ -417,7 +635,22 @@ impl<'a> Parser<'a> {
         };
         
         let left = match left {
-            Expr::Var(v) => v,
+            Expr::Var { name, .. } => name,
+            Expr::Tuple { items, .. } | Expr::List { items, .. } if op.is_none() => {
+                let mut targets = vec![];
+                for item in items {
+                    match item {
+                        Expr::Var { name, .. } => targets.push(name),
+                        _ => return Err(ParseError {
+                            msg: "can only unpack to variables".to_string(),
+                            token_idx: item.token_idx().unwrap_or(token_idx),
+                        }),
+                    }
+                }
+                self.consume();
+                let right = self.parse_expr_list()?;
+                return Ok(Stmt::Unpack { token_idx, targets, right });
+            }
             _ => return Err(ParseError {
                 msg: "can only assign to variables".to_string(),
                 token_idx: self.peek_token_idx(),
@@ -425,10 +658,10 @@ impl<'a> Parser<'a> {
         };
 
         self.consume();
-        let right = self.parse_expr()?;
+        let right = self.parse_expr_list()?;
 
         Ok(Stmt::Assign {
-            left, right, op
+            token_idx, left, right, op
         })
     }
 
@@ -436,55 +669,123 @@ impl<'a> Parser<'a> {
         self.parse_expr_bp(i32::MIN)
     }
 
+    // Comma-separated expressions without parentheses, as in 'return a, b'.
+    // A single expression without a trailing comma is not a tuple.
+    fn parse_expr_list(&mut self) -> Result<Expr, ParseError> {
+        let token_idx = self.peek_token_idx();
+        let first = self.parse_expr()?;
+        if self.peek_token() != (TokenKind::Punct, ",") {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.peek_token() == (TokenKind::Punct, ",") {
+            self.consume();
+            match self.peek_token() {
+                (TokenKind::Newline, _) | (TokenKind::Punct, "=") => break,
+                _ => items.push(self.parse_expr()?),
+            }
+        }
+        Ok(Expr::Tuple { items, token_idx })
+    }
+
+    // Parses items up to and including the closing bracket.
+    // Returns the items and whether there was a trailing comma.
+    fn parse_items(&mut self, close: &str) -> Result<(Vec<Expr>, bool), ParseError> {
+        let mut items = vec![];
+        loop {
+            if self.peek_token() == (TokenKind::Punct, close) {
+                self.consume();
+                return Ok((items, true));
+            }
+            items.push(self.parse_expr()?);
+            match self.peek_token() {
+                (TokenKind::Punct, p) if p == close => {
+                    self.consume();
+                    return Ok((items, false));
+                }
+                (TokenKind::Punct, ",") => {
+                    self.consume();
+                }
+                _ => return Err(ParseError {
+                    msg: format!("expected ',' or '{}'", close),
+                    token_idx: self.peek_token_idx(),
+                }),
+            }
+        }
+    }
+
     // https://matklad.github.io/2020/04/13/simple-but-powerful-pratt-parsing.html
     fn parse_expr_bp(&mut self, min_bp: i32) -> Result<Expr, ParseError> {
         let mut lhs = match self.peek_token() {
             (TokenKind::Numeral, s) => {
                 let token_idx = self.peek_token_idx();
                 let c: i32 = s.parse()
-                    .map_err(|e| ParseError {
-                        msg: format!("{:?}", e),
+                    .map_err(|_| ParseError {
+                        msg: "integer literal is too large".to_string(),
                         token_idx,
                     })?;
                 self.consume();
-                Expr::Const(Value::Int(c))
+                Expr::Const { value: Value::Int(c), token_idx }
             }
             (TokenKind::Literal, s) => {
                 let value = s
                     .strip_prefix('\'').unwrap()
                     .strip_suffix('\'').unwrap()
                     .to_string();
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                Expr::Const(Value::String(value))
+                Expr::Const { value: Value::String(value), token_idx }
             }
             (TokenKind::Keyword, "None") => {
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                Expr::Const(Value::None)
+                Expr::Const { value: Value::None, token_idx }
             }
             (TokenKind::Keyword, "True") => {
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                Expr::Const(Value::Bool(true))
+                Expr::Const { value: Value::Bool(true), token_idx }
             }
             (TokenKind::Keyword, "False") => {
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                Expr::Const(Value::Bool(false))
+                Expr::Const { value: Value::Bool(false), token_idx }
             }
             (TokenKind::Iden, name) => {
                 let name = name.to_owned();
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                Expr::Var(name)
+                Expr::Var { name, token_idx }
             }
             (TokenKind::Punct, "(") => {
+                let token_idx = self.peek_token_idx();
                 self.consume();
-                let e = self.parse_expr()?;
-                self.consume_expected(TokenKind::Punct, ")")?;
-                e
+                let (mut items, trailing_comma) = self.parse_items(")")?;
+                if items.len() == 1 && !trailing_comma {
+                    items.pop().unwrap()
+                } else {
+                    Expr::Tuple { items, token_idx }
+                }
+            }
+            (TokenKind::Punct, "[") => {
+                let open_bracket_token_idx = self.peek_token_idx();
+                self.consume();
+                let (items, _) = self.parse_items("]")?;
+                Expr::List { items, open_bracket_token_idx }
             }
             (TokenKind::Keyword, "not") => {
+                let op_token_idx = self.peek_token_idx();
                 self.consume();
                 let r_bp = 301;
                 let arg = Box::new(self.parse_expr_bp(r_bp)?);
-                Expr::UnaryOp { op: "not", arg }
+                Expr::UnaryOp { op: "not", op_token_idx, arg }
+            }
+            (TokenKind::Punct, "-") => {
+                let op_token_idx = self.peek_token_idx();
+                self.consume();
+                let r_bp = 651;
+                let arg = Box::new(self.parse_expr_bp(r_bp)?);
+                Expr::UnaryOp { op: "-", op_token_idx, arg }
             }
             _ => return Err(ParseError {
                 msg: "expected expression".to_string(),
@@ -528,6 +829,59 @@ impl<'a> Parser<'a> {
                 continue;
             }
 
+            if self.peek_token() == (TokenKind::Punct, "[") {
+                let open_bracket_token_idx = self.peek_token_idx();
+                let l_bp = 700;
+                assert_ne!(l_bp, min_bp, "ambiguous binding power");
+                if l_bp < min_bp {
+                    break;
+                }
+                self.consume();
+                let index = self.parse_expr()?;
+                self.consume_expected(TokenKind::Punct, "]")?;
+                lhs = Expr::Index {
+                    value: Box::new(lhs),
+                    open_bracket_token_idx,
+                    index: Box::new(index),
+                };
+                continue;
+            }
+
+            if self.peek_token() == (TokenKind::Punct, ".") {
+                let dot_token_idx = self.peek_token_idx();
+                let l_bp = 700;
+                assert_ne!(l_bp, min_bp, "ambiguous binding power");
+                if l_bp < min_bp {
+                    break;
+                }
+                self.consume();
+                let name = self.parse_name("expected attribute name")?;
+                lhs = Expr::Attr {
+                    value: Box::new(lhs),
+                    dot_token_idx,
+                    name,
+                };
+                continue;
+            }
+
+            if self.peek_token() == (TokenKind::Keyword, "if") {
+                let l_bp = 50;
+                assert_ne!(l_bp, min_bp, "ambiguous binding power");
+                if l_bp < min_bp {
+                    break;
+                }
+                self.consume();
+                let cond = self.parse_expr_bp(51)?;
+                self.consume_expected(TokenKind::Keyword, "else")?;
+                let els = self.parse_expr_bp(49)?;
+                lhs = Expr::IfExp {
+                    cond: Box::new(cond),
+                    then: Box::new(lhs),
+                    els: Box::new(els),
+                };
+                continue;
+            }
+
             let (op, l_bp, r_bp) = match self.peek_token() {
                 (TokenKind::Keyword, "or") => ("or", 100, 101),
                 (TokenKind::Keyword, "and") => ("and", 200, 201),
@@ -611,10 +965,33 @@ mod tests {
 
         check_parse_expr("not 1 + 2", "(not (+ 1 2))");
 
+        check_parse_expr("-1", "(- 1)");
+        check_parse_expr("-1 * 2", "(* (- 1) 2)");
+        check_parse_expr("1 - -2", "(- 1 (- 2))");
+
+        check_parse_expr("()", "(tuple)");
+        check_parse_expr("(1,)", "(tuple 1)");
+        check_parse_expr("(1, 2 + 3)", "(tuple 1 (+ 2 3))");
+        check_parse_expr("[]", "(list)");
+        check_parse_expr("[1]", "(list 1)");
+        check_parse_expr("[1, [2],]", "(list 1 (list 2))");
+        check_parse_expr("a[0]", "(index a 0)");
+        check_parse_expr("f(x)[i + 1][2]", "(index (index (call f x) (+ i 1)) 2)");
+        check_parse_expr("-a[0]", "(- (index a 0))");
+
+        check_parse_expr("1 if c else 2", "(ifexp c 1 2)");
+        check_parse_expr("1 + 2 if c or d else 3", "(ifexp (or c d) (+ 1 2) 3)");
+        check_parse_expr("1 if a else 2 if b else 3", "(ifexp a 1 (ifexp b 2 3))");
+        check_parse_expr("(1 if a else 2) if b else 3", "(ifexp b (ifexp a 1 2) 3)");
+
         check_parse_expr("f()", "(call f)");
         check_parse_expr("f(x)", "(call f x)");
         check_parse_expr("f(x,)", "(call f x)");
         check_parse_expr("f(x, y)", "(call f x y)");
+
+        check_parse_expr("m.x", "(. m x)");
+        check_parse_expr("m.f(x)[0]", "(index (call (. m f) x) 0)");
+        check_parse_expr("-m.x * 2", "(* (- (. m x)) 2)");
     }
 
     #[test]
@@ -622,12 +999,18 @@ mod tests {
         check_parse_block("7", "7");
         check_parse_block("a = 7", "(= a 7)");
         check_parse_block("a += 1", "(+= a 1)");
+        check_parse_block("a = 1, 2", "(= a (tuple 1 2))");
+        check_parse_block("a, b = b, a", "(= (a b) (tuple b a))");
+        check_parse_block("(a, b) = t", "(= (a b) t)");
+        check_parse_block("[a] = t", "(= (a) t)");
+        check_parse_block("1, 2", "(tuple 1 2)");
     }
 
     #[test]
     fn control_flow() {
         check_parse_block("return", "return");
         check_parse_block("return 42", "(return 42)");
+        check_parse_block("return 4, 2", "(return (tuple 4 2))");
         check_parse_block("
             pass
             break
@@ -658,7 +1041,42 @@ mod tests {
             while True:
                 pass
             ",
-            "(while true pass)")
+            "(while true pass)");
+
+        check_parse_block("
+            if 1:
+                2
+            elif 3:
+                4
+            elif 5:
+                6
+            else:
+                7
+            ",
+            "(if 1 2 (if 3 4 (if 5 6 7)))");
+        check_parse_block("
+            if 1:
+                2
+            elif 3:
+                4
+            ",
+            "(if 1 2 (if 3 4))");
+
+        check_parse_block("
+            for i in range(n):
+                pass
+            ",
+            "(for i 0 n 1 pass)");
+        check_parse_block("
+            for i in range(1, n + 1):
+                pass
+            ",
+            "(for i 1 (+ n 1) 1 pass)");
+        check_parse_block("
+            for i in range(10, 0, -2):
+                pass
+            ",
+            "(for i 10 0 (- 2) pass)");
     }
 
     #[test]
@@ -690,4 +1108,11 @@ mod tests {
         check_parse_block("global x", "(global x)");
         check_parse_block("global x, y", "(global x y)");
     }
+
+    #[test]
+    fn import() {
+        check_parse_block("import nav", "(import nav)");
+        check_parse_block("from nav import f", "(from nav import f)");
+        check_parse_block("from nav import f, X", "(from nav import f X)");
+    }
 }
@@ -1,44 +1,118 @@
 #![allow(dead_code)]
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use super::tokenizer::{tokenize, Token, TokenizerError};
-use super::ast::{Parser, Expr, Stmt};
+use super::ast::{Parser, Expr, Stmt, Block};
 use super::vm::Value;
+use super::ant_io;
 
-struct LoadedFile<'a> {
-    filename: &'a str,
-    text: &'a str,
+struct LoadedFile {
+    filename: String,
+    text: String,
+    module: Option<String>,  // None for files sharing the top-level namespace
     tokens: Option<Vec<Token>>,
 }
 
-pub struct LoadedFiles<'a> {
-    files: Vec<LoadedFile<'a>>,
+pub struct LoadedFiles {
+    files: Vec<LoadedFile>,
+    search_path: Vec<PathBuf>,
 }
 
-impl<'a> LoadedFiles<'a> {
-    pub fn new(files: &[(&'a str, &'a str)]) -> Self {
+impl LoadedFiles {
+    // Files sharing the top-level namespace, like the prelude and the ant itself.
+    pub fn new(files: &[(&str, &str)]) -> Self {
         LoadedFiles {
             files: files.iter().map(|&(filename, text)| LoadedFile {
-                filename,
-                text,
+                filename: filename.to_string(),
+                text: text.to_string(),
+                module: None,
                 tokens: None,
             }).collect(),
+            search_path: vec![],
         }
     }
 
+    // Where to look for 'name.py' on 'import name'.
+    pub fn add_search_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.search_path.push(dir.into());
+    }
+
+    // Module that is not on disk.
+    pub fn add_module(&mut self, name: &str, filename: &str, text: &str) {
+        assert!(self.module_idx(name).is_none());
+        self.files.push(LoadedFile {
+            filename: filename.to_string(),
+            text: text.to_string(),
+            module: Some(name.to_string()),
+            tokens: None,
+        });
+    }
+
+    fn module_idx(&self, name: &str) -> Option<usize> {
+        self.files.iter().position(|f| f.module.as_deref() == Some(name))
+    }
+
+    fn find_module(&mut self, name: &str) -> Option<usize> {
+        if let Some(idx) = self.module_idx(name) {
+            return Some(idx);
+        }
+        for dir in &self.search_path {
+            let path = dir.join(format!("{}.py", name));
+            if let Ok(text) = std::fs::read_to_string(&path) {
+                self.files.push(LoadedFile {
+                    filename: path.to_string_lossy().into_owned(),
+                    text,
+                    module: Some(name.to_string()),
+                    tokens: None,
+                });
+                return Some(self.files.len() - 1);
+            }
+        }
+        None
+    }
+
+    // End of the last top-level file, the ant itself.
+    pub fn end_loc(&self) -> Loc {
+        let file_idx = self.files.iter().rposition(|f| f.module.is_none()).unwrap();
+        Loc { file_idx, pos: self.files[file_idx].text.len() }
+    }
+
     fn render_loc(&self, loc: Loc) -> String {
         let file = &self.files[loc.file_idx];
-        let (row, col) = byte_pos_to_row_col(loc.pos, file.text);
+        let (row, col) = byte_pos_to_row_col(loc.pos, &file.text);
         format!("{}:{}:{}", file.filename, row + 1, col + 1)
     }
 
+    pub fn render_file_line(&self, loc: Loc) -> String {
+        let (filename, line) = self.file_line(loc);
+        format!("{}:{}", filename, line)
+    }
+
+    // Line numbers start from 1.
+    pub fn file_line(&self, loc: Loc) -> (&str, usize) {
+        let file = &self.files[loc.file_idx];
+        let (row, _) = byte_pos_to_row_col(loc.pos, &file.text);
+        (&file.filename, row + 1)
+    }
+
+    // Error message followed by the offending line with a caret under the error position.
     pub fn render_error(&self, e: AnyError) -> String {
-        format!("{}  {}", self.render_loc(e.loc), e.msg)
+        let text = &self.files[e.loc.file_idx].text;
+        let line_start = text[..e.loc.pos].rfind('\n').map_or(0, |p| p + 1);
+        let line_end = text[e.loc.pos..].find('\n').map_or(text.len(), |p| e.loc.pos + p);
+        let caret_indent: String = text[line_start..e.loc.pos].chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("{}  {}\n    {}\n    {}^",
+            self.render_loc(e.loc), e.msg,
+            &text[line_start..line_end],
+            caret_indent)
     }
 }
 
 #[derive(Clone, Copy, Debug)]
-struct Loc {
+pub struct Loc {
     file_idx: usize,
     pos: usize,
 }
@@ -82,12 +156,19 @@ pub enum Insn {
     PopGlobal(String),
     PushLocal(usize),
     PopLocal(usize),
+    ForgetGlobal(String),
+    ForgetLocal(usize),
+    BuildTuple(usize),
+    BuildList(usize),
+    Unpack(usize),
     Jump(isize),
     JumpIfFalse(isize),
     Call {
         f_idx: usize,
         num_args: usize,
     },
+    // Pops the arguments of an ant IO builtin and pushes the instruction.
+    AntInsn(&'static str),
     Input,
     Output,
 }
@@ -117,12 +198,21 @@ impl Insn {
                 let (al, name) = cf.unwrap().arg_or_local_name_by_idx(i);
                 format!("pop {} {}", al, name)
             }
+            Insn::ForgetGlobal(v) => format!("forget global {}", v),
+            &Insn::ForgetLocal(i) => {
+                let (al, name) = cf.unwrap().arg_or_local_name_by_idx(i);
+                format!("forget {} {}", al, name)
+            }
+            Insn::BuildTuple(n) => format!("build tuple of {}", n),
+            Insn::BuildList(n) => format!("build list of {}", n),
+            Insn::Unpack(n) => format!("unpack {}", n),
             Insn::Jump(_) =>
                 format!("jump label_{}", self.jump_target(addr).unwrap()),
             Insn::JumpIfFalse(_) =>
                 format!("pop and jump if false label_{}", self.jump_target(addr).unwrap()),
             &Insn::Call { f_idx, num_args } =>
                 format!("call {} with {} args", cp.functions[f_idx].name, num_args),
+            Insn::AntInsn(name) => format!("make {} insn", name),
             Insn::Input => "input".to_string(),
             Insn::Output => "output".to_string(),
         }
@@ -135,6 +225,7 @@ pub struct CompiledFunction {
     pub arg_names: Vec<String>,
     pub local_names: Vec<String>,
     pub insns: Vec<Insn>,
+    locs: Vec<Loc>,  // parallel to insns
 }
 
 impl CompiledFunction {
@@ -151,46 +242,69 @@ impl CompiledFunction {
 pub struct CompiledProgram {
     pub functions: Vec<CompiledFunction>,
     pub insns: Vec<Insn>,
+    locs: Vec<Loc>,  // parallel to insns
 }
 
 impl CompiledProgram {
-    pub fn new(lfs: &mut LoadedFiles) -> Result<Self, AnyError> {
-        let mut functions: Vec<CompiledFunction> = vec![];
+    // Runtime error at the instruction with address addr
+    // in the function fn_idx (None for the entry point).
+    pub fn runtime_error(&self, msg: String, fn_idx: Option<usize>, addr: usize) -> AnyError {
+        self.runtime_error_at(msg, self.insn_loc(fn_idx, addr))
+    }
+
+    pub fn runtime_error_at(&self, msg: String, loc: Loc) -> AnyError {
+        AnyError {
+            msg: format!("runtime error: {}", msg),
+            loc,
+        }
+    }
 
+    pub fn insn_loc(&self, fn_idx: Option<usize>, addr: usize) -> Loc {
+        match fn_idx {
+            None => self.locs[addr],
+            Some(fn_idx) => self.functions[fn_idx].locs[addr],
+        }
+    }
+
+    pub fn new(lfs: &mut LoadedFiles) -> Result<Self, AnyError> {
+        // Modules imported by the files are appended to lfs.files as we go.
         let mut asts = vec![];
-        for (file_idx, file) in lfs.files.iter_mut().enumerate() {
-            let tokens = tokenize(file.text).map_err(|e| {
-                let (msg, pos) = match e {
-                    TokenizerError::UnrecognizedToken { pos } => ("unrecognized token", pos),
-                    TokenizerError::InconsistentIndent { pos } => ("inconsistent indentation", pos),
-                    TokenizerError::MismatchedQuote { pos } => ("mismatched quote", pos),
-                };
-                AnyError {
-                    msg: format!("lexical error: {}", msg),
-                    loc: Loc { file_idx, pos },
-                }
-            })?;
-            let mut parser = Parser::new(file.text, &tokens);
-            let ast = parser.parse_block().map_err(|e| {
-                let pos = tokens[e.token_idx].start;
-                AnyError {
-                    msg: format!("syntax error: {}", e.msg),
-                    loc: Loc { file_idx, pos }
+        while asts.len() < lfs.files.len() {
+            let file_idx = asts.len();
+            let ast = parse_file(lfs, file_idx)?;
+            for stmt in &ast {
+                if let Stmt::Import { token_idx, module } |
+                       Stmt::FromImport { token_idx, module, .. } = stmt {
+                    if lfs.find_module(module).is_none() {
+                        return Err(AnyError {
+                            msg: format!("module {:?} not found", module),
+                            loc: Loc::from_token(lfs, file_idx, *token_idx),
+                        });
+                    }
                 }
-            })?;
-            file.tokens = Some(tokens);
+            }
             asts.push(ast);
         }
 
+        let mut functions: Vec<CompiledFunction> = vec![];
         for (file_idx, ast) in asts.iter().enumerate() {
+            let prefix = module_prefix(lfs, file_idx);
             for stmt in ast {
                 if let Stmt::Def { token_idx, .. } = stmt {
-                    let cf = precompile_def(stmt).map_err(
+                    let mut cf = precompile_def(stmt).map_err(
                         |CompileError { msg, token_idx }| AnyError {
                             msg,
                             loc: Loc::from_token(lfs, file_idx, token_idx),
                         }
                     )?;
+                    // ant instructions can be shadowed, see compile_expr_impl()
+                    if builtin_num_args(&cf.name).is_some() && ant_io::builtin(&cf.name).is_none() {
+                        return Err(AnyError {
+                            msg: format!("function {:?} shadows a builtin", cf.name),
+                            loc: Loc::from_token(lfs, file_idx, *token_idx),
+                        });
+                    }
+                    cf.name = format!("{}{}", prefix, cf.name);
                     if functions.iter().any(|f| f.name == cf.name) {
                         return Err(AnyError {
                             msg: format!("duplicate function {:?}", cf.name),
@@ -202,17 +316,63 @@ impl CompiledProgram {
             }
         }
 
+        // Imported modules come first, so their scopes are ready
+        // when 'from module import name' is resolved.
+        let mut scopes: Vec<Option<Scope>> = asts.iter().map(|_| None).collect();
+        for file_idx in import_order(lfs, &asts)? {
+            let is_module = lfs.files[file_idx].module.is_some();
+            let mut scope = Scope {
+                prefix: module_prefix(lfs, file_idx),
+                globals: if is_module { module_globals(&asts[file_idx]) } else { HashSet::new() },
+                modules: HashMap::new(),
+                imported_functions: HashMap::new(),
+            };
+            for stmt in &asts[file_idx] {
+                match stmt {
+                    Stmt::Import { module, .. } => {
+                        scope.modules.insert(module.clone(), lfs.module_idx(module).unwrap());
+                    }
+                    Stmt::FromImport { token_idx, module, names } => {
+                        let m_scope = scopes[lfs.module_idx(module).unwrap()].as_ref().unwrap();
+                        for name in names {
+                            if let Some(f_idx) = m_scope.function_idx(name, &functions) {
+                                scope.imported_functions.insert(name.clone(), f_idx);
+                            } else if m_scope.globals.contains(name) {
+                                if is_module {
+                                    scope.globals.insert(name.clone());
+                                }
+                            } else {
+                                return Err(AnyError {
+                                    msg: format!("module {:?} has no name {:?}", module, name),
+                                    loc: Loc::from_token(lfs, file_idx, *token_idx),
+                                });
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            scopes[file_idx] = Some(scope);
+        }
+        let scopes: Vec<Scope> = scopes.into_iter().map(Option::unwrap).collect();
+
         for (file_idx, ast) in asts.iter().enumerate() {
+            let scope = &scopes[file_idx];
             for stmt in ast {
-                let (name, body) = match stmt {
-                    Stmt::Def { name, body, .. } => (name, body),
+                let (token_idx, name, body) = match stmt {
+                    Stmt::Def { token_idx, name, body, .. } => (token_idx, name, body),
                     _ => continue
                 };
-                let cf = functions.iter().find(|cf| cf.name == *name).unwrap();
+                let full_name = format!("{}{}", scope.prefix, name);
+                let cf = functions.iter().find(|cf| cf.name == full_name).unwrap();
                 let mut ctx = Ctx {
                     functions: &functions,
+                    scopes: &scopes,
+                    scope,
                     current_function: Some(cf),
                     insns: vec![],
+                    locs: vec![],
+                    cur_token: *token_idx,
                     break_label: None,
                     continue_label: None,
                 };
@@ -220,7 +380,7 @@ impl CompiledProgram {
                 if let Some(Stmt::Return { .. }) = body.last() {
                 } else {
                     // implicit return
-                    ctx.insns.push(Insn::PushConst(Value::None));
+                    ctx.emit(Insn::PushConst(Value::None));
                 }
                 for stmt in body.iter().rev() {
                     match stmt {
@@ -235,45 +395,237 @@ impl CompiledProgram {
                 }
                 ctx.insns.reverse();
                 let insns = ctx.insns;
-                let cf = functions.iter_mut().find(|cf| cf.name == *name).unwrap();
+                let locs = ctx.locs.iter().rev()
+                    .map(|&token_idx| Loc::from_token(lfs, file_idx, token_idx))
+                    .collect();
+                let cf = functions.iter_mut().find(|cf| cf.name == full_name).unwrap();
                 cf.insns = insns;
+                cf.locs = locs;
+            }
+        }
+
+        let mut top_level = TopLevel {
+            lfs,
+            asts: &asts,
+            functions: &functions,
+            scopes: &scopes,
+            executed_modules: HashSet::new(),
+            insns: vec![],
+            locs: vec![],
+        };
+        for file_idx in 0..asts.len() {
+            if lfs.files[file_idx].module.is_none() {
+                top_level.compile_file(file_idx)?;
+            }
+        }
+        let TopLevel { insns, locs, .. } = top_level;
+
+        Ok(CompiledProgram {
+            functions,
+            insns,
+            locs,
+        })
+    }
+}
+
+fn parse_file(lfs: &mut LoadedFiles, file_idx: usize) -> Result<Block, AnyError> {
+    let file = &mut lfs.files[file_idx];
+    let tokens = tokenize(&file.text).map_err(|e| {
+        let (msg, pos) = match e {
+            TokenizerError::UnrecognizedToken { pos } => ("unrecognized token", pos),
+            TokenizerError::InconsistentIndent { pos } => ("inconsistent indentation", pos),
+            TokenizerError::MismatchedQuote { pos } => ("mismatched quote", pos),
+        };
+        AnyError {
+            msg: format!("lexical error: {}", msg),
+            loc: Loc { file_idx, pos },
+        }
+    })?;
+    let mut parser = Parser::new(&file.text, &tokens);
+    let ast = parser.parse_block().map_err(|e| {
+        let pos = tokens[e.token_idx].start;
+        AnyError {
+            msg: format!("syntax error: {}", e.msg),
+            loc: Loc { file_idx, pos }
+        }
+    })?;
+    file.tokens = Some(tokens);
+    Ok(ast)
+}
+
+// Functions and globals of module 'nav' are called 'nav.f' and 'nav.x'.
+fn module_prefix(lfs: &LoadedFiles, file_idx: usize) -> String {
+    match &lfs.files[file_idx].module {
+        Some(module) => format!("{}.", module),
+        None => String::new(),
+    }
+}
+
+// Files in an order where modules come before the files importing them.
+fn import_order(lfs: &LoadedFiles, asts: &[Block]) -> Result<Vec<usize>, AnyError> {
+    fn visit(
+        file_idx: usize,
+        lfs: &LoadedFiles,
+        asts: &[Block],
+        in_progress: &mut Vec<bool>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), AnyError> {
+        in_progress[file_idx] = true;
+        for stmt in &asts[file_idx] {
+            if let Stmt::Import { token_idx, module } |
+                   Stmt::FromImport { token_idx, module, .. } = stmt {
+                let m = lfs.module_idx(module).unwrap();
+                if in_progress[m] {
+                    return Err(AnyError {
+                        msg: format!("circular import of module {:?}", module),
+                        loc: Loc::from_token(lfs, file_idx, *token_idx),
+                    });
+                }
+                if !order.contains(&m) {
+                    visit(m, lfs, asts, in_progress, order)?;
+                }
             }
         }
+        in_progress[file_idx] = false;
+        order.push(file_idx);
+        Ok(())
+    }
+
+    let mut in_progress = vec![false; asts.len()];
+    let mut order = vec![];
+    for file_idx in 0..asts.len() {
+        if !order.contains(&file_idx) {
+            visit(file_idx, lfs, asts, &mut in_progress, &mut order)?;
+        }
+    }
+    Ok(order)
+}
 
-        let mut insns = vec![];
-        for (file_idx, ast) in asts.iter().enumerate().rev() {
+// Global variables of a module: assigned at its top level,
+// or declared global in its functions.
+fn module_globals(ast: &[Stmt]) -> HashSet<String> {
+    fn visit(stmt: &Stmt, globals: &mut HashSet<String>) {
+        match stmt {
+            Stmt::Assign { left, .. } => {
+                globals.insert(left.clone());
+            }
+            Stmt::Unpack { targets, .. } => globals.extend(targets.iter().cloned()),
+            Stmt::If { then, els, .. } => {
+                for stmt in then.iter().chain(els.iter().flatten()) {
+                    visit(stmt, globals);
+                }
+            }
+            Stmt::While { body, .. } => {
+                for stmt in body {
+                    visit(stmt, globals);
+                }
+            }
+            Stmt::For { token_idx, var, body, .. } => {
+                globals.insert(var.clone());
+                globals.insert(for_counter_name(*token_idx));
+                globals.insert(for_stop_name(*token_idx));
+                for stmt in body {
+                    visit(stmt, globals);
+                }
+            }
+            Stmt::Def { body, .. } => {
+                for stmt in body {
+                    if let Stmt::Global { vars, .. } = stmt {
+                        globals.extend(vars.iter().cloned());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut globals = HashSet::new();
+    for stmt in ast {
+        visit(stmt, &mut globals);
+    }
+    globals
+}
+
+// Names visible in a file.
+struct Scope {
+    prefix: String,  // see module_prefix()
+    globals: HashSet<String>,  // own globals of a module, empty for other files
+    modules: HashMap<String, usize>,  // 'import module', to file indices
+    imported_functions: HashMap<String, usize>,  // 'from module import f'
+}
+
+impl Scope {
+    fn global_name(&self, name: &str) -> String {
+        if self.globals.contains(name) {
+            format!("{}{}", self.prefix, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    // Function defined in or imported into this file.
+    fn function_idx(&self, name: &str, functions: &[CompiledFunction]) -> Option<usize> {
+        if let Some(&f_idx) = self.imported_functions.get(name) {
+            return Some(f_idx);
+        }
+        let full_name = format!("{}{}", self.prefix, name);
+        functions.iter().position(|cf| cf.name == full_name)
+    }
+}
+
+// Entry point code. The top level of each module runs
+// at its first import, like in Python.
+struct TopLevel<'a> {
+    lfs: &'a LoadedFiles,
+    asts: &'a [Block],
+    functions: &'a [CompiledFunction],
+    scopes: &'a [Scope],
+    executed_modules: HashSet<usize>,
+    insns: Vec<Insn>,
+    locs: Vec<Loc>,
+}
+
+impl<'a> TopLevel<'a> {
+    fn compile_file(&mut self, file_idx: usize) -> Result<(), AnyError> {
+        let asts = self.asts;
+        let scopes = self.scopes;
+        for stmt in &asts[file_idx] {
             let mut ctx = Ctx {
-                functions: &functions,
+                functions: self.functions,
+                scopes,
+                scope: &scopes[file_idx],
                 current_function: None,
                 insns: vec![],
+                locs: vec![],
+                cur_token: 0,
                 break_label: None,
                 continue_label: None,
             };
-            for stmt in ast.iter().rev() {
-                match stmt {
-                    Stmt::Def { .. } => {}
-                    Stmt::Global { token_idx, ..} => {
-                        return Err(AnyError {
-                            msg: "global outside of function".to_string(),
-                            loc: Loc::from_token(lfs, file_idx, *token_idx),
-                        })
+            let res = match stmt {
+                Stmt::Def { .. } => Ok(()),
+                Stmt::Global { token_idx, ..} => Err(CompileError {
+                    msg: "global outside of function".to_string(),
+                    token_idx: *token_idx,
+                }),
+                Stmt::Import { module, .. } |
+                Stmt::FromImport { module, .. } => {
+                    let m = self.lfs.module_idx(module).unwrap();
+                    if self.executed_modules.insert(m) {
+                        self.compile_file(m)?;
                     }
-                    _ => compile_stmt(stmt, &mut ctx).map_err(
-                        |CompileError { msg, token_idx }| AnyError {
-                            msg,
-                            loc: Loc::from_token(lfs, file_idx, token_idx),
-                        }
-                    )?,
+                    compile_import(stmt, &scopes[m], &mut ctx)
                 }
-            }
-            insns.extend(ctx.insns);
+                _ => compile_stmt(stmt, &mut ctx),
+            };
+            res.map_err(|CompileError { msg, token_idx }| AnyError {
+                msg,
+                loc: Loc::from_token(self.lfs, file_idx, token_idx),
+            })?;
+            self.insns.extend(ctx.insns.into_iter().rev());
+            let lfs = self.lfs;
+            self.locs.extend(ctx.locs.iter().rev()
+                .map(|&token_idx| Loc::from_token(lfs, file_idx, token_idx)));
         }
-        insns.reverse();
-
-        Ok(CompiledProgram {
-            functions,
-            insns,
-        })
+        Ok(())
     }
 }
 
@@ -327,6 +679,7 @@ fn precompile_def(stmt: &Stmt)
         arg_names: args.clone(),
         local_names: vec![],
         insns: vec![],
+        locs: vec![],
     };
 
     let mut globals = HashSet::new();
@@ -352,6 +705,7 @@ fn precompile_def(stmt: &Stmt)
             Stmt::Continue { .. } => {}
             Stmt::Return { .. } => {}
             Stmt::Expr(_) => {}
+            Stmt::Import { .. } | Stmt::FromImport { .. } => {}
             Stmt::Assign { left, .. } => {
                 if !globals.contains(left) {
                     // TODO: quadratic
@@ -361,6 +715,15 @@ fn precompile_def(stmt: &Stmt)
                     }
                 }
             }
+            Stmt::Unpack { targets, .. } => {
+                for left in targets {
+                    if !globals.contains(left) &&
+                       !cf.arg_names.contains(left) &&
+                       !cf.local_names.contains(left) {
+                        cf.local_names.push(left.clone());
+                    }
+                }
+            }
             Stmt::If { cond: _, then, els } => {
                 for stmt in then {
                     visit(stmt, globals, cf);
@@ -376,6 +739,19 @@ fn precompile_def(stmt: &Stmt)
                     visit(stmt, globals, cf);
                 }
             }
+            Stmt::For { token_idx, var, body, .. } => {
+                let hidden = [for_counter_name(*token_idx), for_stop_name(*token_idx)];
+                for name in std::iter::once(var).chain(&hidden) {
+                    if !globals.contains(name) &&
+                       !cf.arg_names.contains(name) &&
+                       !cf.local_names.contains(name) {
+                        cf.local_names.push(name.clone());
+                    }
+                }
+                for stmt in body {
+                    visit(stmt, globals, cf);
+                }
+            }
             Stmt::Def { .. } => {}
             Stmt::Global { .. } => {}
         }
@@ -388,20 +764,57 @@ fn precompile_def(stmt: &Stmt)
     Ok(cf)
 }
 
+// Hidden variables of a 'for' loop. They are not valid identifiers,
+// so they can't clash with user variables.
+fn for_counter_name(token_idx: usize) -> String {
+    format!("for@{}.counter", token_idx)
+}
+
+fn for_stop_name(token_idx: usize) -> String {
+    format!("for@{}.stop", token_idx)
+}
+
 struct Ctx<'a> {
     functions: &'a [CompiledFunction],
+    scopes: &'a [Scope],
+    scope: &'a Scope,
     current_function: Option<&'a CompiledFunction>,
 
     insns: Vec<Insn>,  // reversed
+    locs: Vec<usize>,  // token indices, parallel to insns
+    cur_token: usize,  // what to blame for runtime errors in emitted insns
     break_label: Option<usize>,
     continue_label: Option<usize>,
 }
 
 impl<'a> Ctx<'a> {
+    fn emit(&mut self, insn: Insn) {
+        self.insns.push(insn);
+        self.locs.push(self.cur_token);
+    }
+
+    // Runs f with cur_token set to token_idx (if any).
+    fn at<T>(&mut self, token_idx: Option<usize>, f: impl FnOnce(&mut Self) -> T) -> T {
+        let old_token = self.cur_token;
+        if let Some(token_idx) = token_idx {
+            self.cur_token = token_idx;
+        }
+        let res = f(self);
+        self.cur_token = old_token;
+        res
+    }
+
     fn label(&self) -> usize {
         self.insns.len()
     }
 
+    // Drops the code emitted after the label, for unreachable code
+    // that was compiled for errors only.
+    fn drop_code(&mut self, label: usize) {
+        self.insns.truncate(label);
+        self.locs.truncate(label);
+    }
+
     fn offset_to(&self, label: usize) -> isize {
         self.label() as isize - label as isize
     }
@@ -409,7 +822,7 @@ impl<'a> Ctx<'a> {
     fn emit_jump(&mut self, label: usize) {
         let d = self.offset_to(label);
         if d != 0 {
-            self.insns.push(Insn::Jump(d));
+            self.emit(Insn::Jump(d));
         }
     }
 
@@ -417,97 +830,306 @@ impl<'a> Ctx<'a> {
         if let Some(cf) = self.current_function {
             // TODO: quadratic
             if let Some(i) = cf.arg_names.iter().position(|a| a == var) {
-                self.insns.push(Insn::PushLocal(i));
+                self.emit(Insn::PushLocal(i));
                 return;
             }
             if let Some(i) = cf.local_names.iter().position(|a| a == var) {
-                self.insns.push(Insn::PushLocal(cf.arg_names.len() + i));
+                self.emit(Insn::PushLocal(cf.arg_names.len() + i));
                 return;
             }
         }
-        self.insns.push(Insn::PushGlobal(var.to_string()));
+        self.emit(Insn::PushGlobal(self.scope.global_name(var)));
     }
 
     fn emit_pop_var(&mut self, var: &str) {
         if let Some(cf) = self.current_function {
             // TODO: quadratic
             if let Some(i) = cf.arg_names.iter().position(|a| a == var) {
-                self.insns.push(Insn::PopLocal(i));
+                self.emit(Insn::PopLocal(i));
+                return;
+            }
+            if let Some(i) = cf.local_names.iter().position(|a| a == var) {
+                self.emit(Insn::PopLocal(cf.arg_names.len() + i));
+                return;
+            }
+        }
+        self.emit(Insn::PopGlobal(self.scope.global_name(var)));
+    }
+
+    fn emit_forget_var(&mut self, var: &str) {
+        if let Some(cf) = self.current_function {
+            // TODO: quadratic
+            if let Some(i) = cf.arg_names.iter().position(|a| a == var) {
+                self.emit(Insn::ForgetLocal(i));
                 return;
             }
             if let Some(i) = cf.local_names.iter().position(|a| a == var) {
-                self.insns.push(Insn::PopLocal(cf.arg_names.len() + i));
+                self.emit(Insn::ForgetLocal(cf.arg_names.len() + i));
                 return;
             }
         }
-        self.insns.push(Insn::PopGlobal(var.to_string()));
+        self.emit(Insn::ForgetGlobal(self.scope.global_name(var)));
+    }
+
+    // Own functions of the file first, then the top-level namespace.
+    fn function_idx(&self, name: &str) -> Option<usize> {
+        self.scope.function_idx(name, self.functions)
+            .or_else(|| self.functions.iter().position(|cf| cf.name == name))
+    }
+
+    // Scope of the module in 'module.name'.
+    fn module_scope(&self, value: &Expr, dot_token_idx: usize)
+    -> Result<(&'a str, &'a Scope), CompileError> {
+        if let Expr::Var { name, .. } = value {
+            if let Some((module, &m)) = self.scope.modules.get_key_value(name) {
+                return Ok((module, &self.scopes[m]));
+            }
+        }
+        Err(CompileError {
+            msg: "attribute access is only supported on imported modules".to_string(),
+            token_idx: dot_token_idx,
+        })
     }
 }
 
 fn compile_expr(e: &Expr, ctx: &mut Ctx) -> Result<(), CompileError> {
+    if let Some(c) = const_value(e) {
+        ctx.emit(Insn::PushConst(c));
+        return Ok(());
+    }
+    ctx.at(e.token_idx(), |ctx| compile_expr_impl(e, ctx))
+}
+
+// Value of an expression without variables or calls (except str() and len()),
+// None if there is no such value or computing it fails,
+// in which case the error is left to the runtime.
+fn const_value(e: &Expr) -> Option<Value> {
     match e {
-        Expr::Const(c) => ctx.insns.push(Insn::PushConst(c.clone())),
-        Expr::Var(v) => ctx.emit_push_var(v),
+        Expr::Const { value, .. } => Some(value.clone()),
+        Expr::Var { .. } | Expr::Attr { .. } => None,
+        Expr::UnaryOp { op: "not", arg, .. } => match const_value(arg)? {
+            Value::Bool(b) => Some(Value::Bool(!b)),
+            _ => None,
+        },
+        Expr::UnaryOp { op, arg, .. } => const_value(arg)?.unary_op(op).ok(),
+        Expr::BinaryOp { op, left, right, .. } if *op == "and" || *op == "or" => {
+            match (const_value(left)?, const_value(right)?) {
+                (Value::Bool(a), Value::Bool(b)) =>
+                    Some(Value::Bool(if *op == "and" { a && b } else { a || b })),
+                _ => None,
+            }
+        }
+        Expr::BinaryOp { op, left, right, .. } =>
+            const_value(left)?.binary_op(op, &const_value(right)?).ok(),
+        Expr::Index { value, index, .. } =>
+            const_value(value)?.binary_op("[]", &const_value(index)?).ok(),
+        Expr::Tuple { items, .. } =>
+            Some(Value::Tuple(items.iter().map(const_value).collect::<Option<_>>()?)),
+        Expr::List { items, .. } =>
+            Some(Value::List(items.iter().map(const_value).collect::<Option<_>>()?)),
+        Expr::IfExp { cond, then, els } => match const_value(cond)? {
+            Value::Bool(true) => const_value(then),
+            Value::Bool(false) => const_value(els),
+            _ => None,
+        },
+        Expr::Call { f, args, .. } => match (&**f, &args[..]) {
+            (Expr::Var { name, .. }, [arg]) if name == "str" || name == "len" =>
+                const_value(arg)?.unary_op(name).ok(),
+            _ => None,
+        },
+    }
+}
+
+fn compile_expr_impl(e: &Expr, ctx: &mut Ctx) -> Result<(), CompileError> {
+    match e {
+        Expr::Const { value, .. } => ctx.emit(Insn::PushConst(value.clone())),
+        Expr::Var { name, .. } => ctx.emit_push_var(name),
         Expr::UnaryOp { op: "not", .. } |
         Expr::BinaryOp { op: "and", .. } |
         Expr::BinaryOp { op: "or", .. } => {
             let end_label = ctx.label();
-            ctx.insns.push(Insn::PushConst(Value::Bool(false)));
+            ctx.emit(Insn::PushConst(Value::Bool(false)));
             let false_label = ctx.label();
             ctx.emit_jump(end_label);
-            ctx.insns.push(Insn::PushConst(Value::Bool(true)));
+            ctx.emit(Insn::PushConst(Value::Bool(true)));
             let true_label = ctx.label();
             compile_cond(e, true_label, false_label, ctx)?;
         }
+        Expr::UnaryOp { op, arg, .. } => {
+            ctx.emit(Insn::UnOp(op));
+            compile_expr(arg, ctx)?;
+        }
         Expr::BinaryOp { op, left, right, .. } => {
-            ctx.insns.push(Insn::BinOp(op));
+            ctx.emit(Insn::BinOp(op));
             compile_expr(right, ctx)?;
             compile_expr(left, ctx)?;
         }
+        Expr::Tuple { items, .. } => {
+            ctx.emit(Insn::BuildTuple(items.len()));
+            for item in items.iter().rev() {
+                compile_expr(item, ctx)?;
+            }
+        }
+        Expr::List { items, .. } => {
+            ctx.emit(Insn::BuildList(items.len()));
+            for item in items.iter().rev() {
+                compile_expr(item, ctx)?;
+            }
+        }
+        Expr::Index { value, index, .. } => {
+            ctx.emit(Insn::BinOp("[]"));
+            compile_expr(index, ctx)?;
+            compile_expr(value, ctx)?;
+        }
+        Expr::Attr { value, dot_token_idx, name } => {
+            let (module, m_scope) = ctx.module_scope(value, *dot_token_idx)?;
+            if !m_scope.globals.contains(name) {
+                return Err(CompileError {
+                    msg: format!("module {:?} has no variable {:?}", module, name),
+                    token_idx: *dot_token_idx,
+                });
+            }
+            ctx.emit(Insn::PushGlobal(m_scope.global_name(name)));
+        }
+        Expr::IfExp { cond, then, els } if matches!(const_value(cond), Some(Value::Bool(_))) => {
+            let (taken, dead) = match const_value(cond) {
+                Some(Value::Bool(true)) => (then, els),
+                _ => (els, then),
+            };
+            let len = ctx.insns.len();
+            compile_expr(dead, ctx)?;
+            ctx.drop_code(len);
+            compile_expr(taken, ctx)?;
+        }
+        Expr::IfExp { cond, then, els } => {
+            let end_label = ctx.label();
+            compile_expr(els, ctx)?;
+            let else_label = ctx.label();
+            ctx.emit_jump(end_label);
+            compile_expr(then, ctx)?;
+            let then_label = ctx.label();
+            compile_cond(cond, then_label, else_label, ctx)?;
+        }
         Expr::Call { f, args, open_paren_token_idx } => {
-            let name = match &**f {
-                Expr::Var(name) => name,
+            let (name, module) = match &**f {
+                Expr::Var { name, .. } => (name, None),
+                Expr::Attr { value, dot_token_idx, name } =>
+                    (name, Some(ctx.module_scope(value, *dot_token_idx)?)),
                 _ => return Err(CompileError {
                     msg: "calling expr that is not a function".to_string(),
                     token_idx: *open_paren_token_idx,
                 }),
             };
 
+            // user functions named like an ant instruction take precedence
+            let ant_insn = match module {
+                Some(_) => None,
+                None => ant_io::builtin(name).filter(|_| ctx.function_idx(name).is_none()),
+            };
+            let builtin_num_args = match module {
+                Some(_) => None,
+                None if ant_io::builtin(name).is_some() => ant_insn.map(|(_, num_args, _)| num_args),
+                None => builtin_num_args(name),
+            };
+            if let Some(num_args) = builtin_num_args {
+                if args.len() != num_args {
+                    return Err(CompileError {
+                        msg: format!("function {:?} takes {} arguments ({} given)",
+                            name, num_args, args.len()),
+                        token_idx: *open_paren_token_idx,
+                    });
+                }
+            }
+
             match name.as_str() {
+                _ if module.is_some() => {}
                 "str" => {
-                    assert_eq!(args.len(), 1);
-                    ctx.insns.push(Insn::UnOp("str"));
+                    ctx.emit(Insn::UnOp("str"));
+                    compile_expr(&args[0], ctx)?;
+                    return Ok(());
+                }
+                "len" => {
+                    ctx.emit(Insn::UnOp("len"));
                     compile_expr(&args[0], ctx)?;
                     return Ok(());
                 }
                 "_output" => {
-                    assert_eq!(args.len(), 1);
-                    ctx.insns.push(Insn::PushConst(Value::None));
-                    ctx.insns.push(Insn::Output);
+                    ctx.emit(Insn::PushConst(Value::None));
+                    ctx.emit(Insn::Output);
                     compile_expr(&args[0], ctx)?;
                     return Ok(());
                 }
                 "_input" => {
-                    assert_eq!(args.len(), 0);
-                    ctx.insns.push(Insn::Input);
+                    ctx.emit(Insn::Input);
+                    return Ok(());
+                }
+                // Like 'del var'. The variable no longer distinguishes
+                // states in py2ant, and reading it is an error.
+                "_forget" => {
+                    let var = match &args[0] {
+                        Expr::Var { name, .. } => name,
+                        _ => return Err(CompileError {
+                            msg: "_forget() argument must be a variable".to_string(),
+                            token_idx: *open_paren_token_idx,
+                        }),
+                    };
+                    ctx.emit(Insn::PushConst(Value::None));
+                    ctx.emit_forget_var(var);
                     return Ok(());
                 }
                 _ => {}
             }
 
-            let f_idx = ctx.functions.iter().position(|cf| cf.name == *name);
-            let f_idx = match f_idx {
-                Some(it) => it,
-                None => return Err(CompileError {
+            if let Some((name, _, returns_success)) = ant_insn {
+                // The ant gets 1 when the instruction succeeded, 0 otherwise.
+                if returns_success {
+                    ctx.emit(Insn::BinOp("=="));
+                    ctx.emit(Insn::PushConst(Value::Int(1)));
+                } else {
+                    ctx.emit(Insn::PushConst(Value::None));
+                    ctx.emit(Insn::Pop);
+                }
+                ctx.emit(Insn::Input);
+                ctx.emit(Insn::Output);
+                let consts: Option<Vec<Value>> = args.iter().map(const_value).collect();
+                match consts {
+                    Some(consts) => {
+                        let insn = ant_io::make_insn(name, &consts).map_err(|(i, msg)| CompileError {
+                            msg,
+                            token_idx: args[i].token_idx().unwrap_or(*open_paren_token_idx),
+                        })?;
+                        ctx.emit(Insn::PushConst(Value::Insn(insn)));
+                    }
+                    None => {
+                        ctx.emit(Insn::AntInsn(name));
+                        for arg in args.iter().rev() {
+                            compile_expr(arg, ctx)?;
+                        }
+                    }
+                }
+                return Ok(());
+            }
+
+            let f_idx = match module {
+                None => ctx.function_idx(name),
+                Some((_, m_scope)) => m_scope.function_idx(name, ctx.functions),
+            };
+            let f_idx = match (f_idx, module) {
+                (Some(it), _) => it,
+                (None, None) => return Err(CompileError {
                     msg: format!("function {:?} not defined", name),
                     token_idx: *open_paren_token_idx,
                 }),
+                (None, Some((module, _))) => return Err(CompileError {
+                    msg: format!("module {:?} has no function {:?}", module, name),
+                    token_idx: *open_paren_token_idx,
+                }),
             };
 
             if ctx.functions[f_idx].arg_names.len() != args.len() {
                 return Err(CompileError {
                     msg: format!("function {:?} takes {} arguments ({} given)",
-                        name,
+                        ctx.functions[f_idx].name,
                         ctx.functions[f_idx].arg_names.len(),
                         args.len(),
                     ),
@@ -515,22 +1137,30 @@ fn compile_expr(e: &Expr, ctx: &mut Ctx) -> Result<(), CompileError> {
                 })
             }
 
-            ctx.insns.push(Insn::Call { f_idx, num_args: args.len() });
+            ctx.emit(Insn::Call { f_idx, num_args: args.len() });
             for arg in args.iter().rev() {
                 compile_expr(arg, ctx)?;
             }
         }
-        _ => todo!(),
     }
     Ok(())
 }
 
+fn builtin_num_args(name: &str) -> Option<usize> {
+    match name {
+        "str" | "len" | "_output" | "_forget" => Some(1),
+        "_input" => Some(0),
+        _ => ant_io::builtin(name).map(|(_, num_args, _)| num_args),
+    }
+}
+
 fn compile_cond(e: &Expr, true_label: usize, false_label: usize, ctx: &mut Ctx) -> Result<(), CompileError> {
+    if let Some(Value::Bool(b)) = const_value(e) {
+        ctx.emit_jump(if b { true_label } else { false_label });
+        return Ok(());
+    }
     match e {
-        Expr::Const(Value::Bool(true)) => {
-            ctx.emit_jump(true_label);
-        }
-        Expr::UnaryOp { op: "not", arg } => {
+        Expr::UnaryOp { op: "not", arg, .. } => {
             compile_cond(arg, false_label, true_label, ctx)?;
         }
         Expr::BinaryOp { op: "and", left, right, .. } => {
@@ -543,11 +1173,11 @@ fn compile_cond(e: &Expr, true_label: usize, false_label: usize, ctx: &mut Ctx)
             let right_label = ctx.label();
             compile_cond(left, true_label, right_label, ctx)?;
         }
-        _ => {
+        _ => ctx.at(e.token_idx(), |ctx| {
             ctx.emit_jump(true_label);
-            ctx.insns.push(Insn::JumpIfFalse(ctx.offset_to(false_label)));
-            compile_expr(e, ctx)?;
-        }
+            ctx.emit(Insn::JumpIfFalse(ctx.offset_to(false_label)));
+            compile_expr(e, ctx)
+        })?,
     }
     Ok(())
 }
@@ -556,18 +1186,38 @@ fn compile_stmt(stmt: &Stmt, ctx: &mut Ctx) -> Result<(), CompileError> {
     match stmt {
         Stmt::Pass => {}
         Stmt::Expr(e) => {
-            ctx.insns.push(Insn::Pop);
+            ctx.emit(Insn::Pop);
             compile_expr(e, ctx)?;
         }
-        Stmt::Assign { left, right, op: None } => {
+        Stmt::Assign { token_idx, left, right, op: None } => ctx.at(Some(*token_idx), |ctx| {
             ctx.emit_pop_var(left);
-            compile_expr(right, ctx)?;
-        }
-        Stmt::Assign { left, right, op: Some(op) } => {
+            compile_expr(right, ctx)
+        })?,
+        Stmt::Unpack { token_idx, targets, right } => ctx.at(Some(*token_idx), |ctx| {
+            for left in targets.iter().rev() {
+                ctx.emit_pop_var(left);
+            }
+            ctx.emit(Insn::Unpack(targets.len()));
+            compile_expr(right, ctx)
+        })?,
+        Stmt::Assign { token_idx, left, right, op: Some(op) } => ctx.at(Some(*token_idx), |ctx| {
             ctx.emit_pop_var(left);
-            ctx.insns.push(Insn::BinOp(op));
+            ctx.emit(Insn::BinOp(op));
             compile_expr(right, ctx)?;
             ctx.emit_push_var(left);
+            Ok(())
+        })?,
+        // the dead branch is only compiled for errors
+        Stmt::If { cond, then, els } if matches!(const_value(cond), Some(Value::Bool(_))) => {
+            let els = els.as_deref().unwrap_or(&[]);
+            let (taken, dead) = match const_value(cond) {
+                Some(Value::Bool(true)) => (&then[..], els),
+                _ => (els, &then[..]),
+            };
+            let len = ctx.insns.len();
+            compile_block(dead, ctx)?;
+            ctx.drop_code(len);
+            compile_block(taken, ctx)?;
         }
         Stmt::If { cond, then, els } => {
             let end_label = ctx.label();
@@ -580,22 +1230,80 @@ fn compile_stmt(stmt: &Stmt, ctx: &mut Ctx) -> Result<(), CompileError> {
             let then_label = ctx.label();
             compile_cond(cond, then_label, else_label, ctx)?;
         }
-        Stmt::While { cond, body } => {
-            let end_label = ctx.label();
-            let jump_back_idx = ctx.insns.len();
-            ctx.insns.push(Insn::Jump(0));
-            let continue_label = ctx.label();
+        Stmt::While { cond, body } if const_value(cond) == Some(Value::Bool(false)) => {
+            let len = ctx.insns.len();
+            compile_loop(cond, &[], body, ctx)?;
+            ctx.drop_code(len);
+        }
+        Stmt::While { cond, body } => compile_loop(cond, &[], body, ctx)?,
+        Stmt::For { token_idx, var, start, stop, step, body } => {
+            // for var in range(start, stop, step):
+            //     body
+            // is compiled as
+            //     counter = start
+            //     stop = stop
+            //     while counter < stop:
+            //         var = counter
+            //         counter += step
+            //         body
+            //     counter = None
+            //     stop = None
+            // so that assigning to var in the body doesn't affect iteration,
+            // and the hidden variables don't multiply states after the loop.
+            let step = match const_value(step) {
+                Some(Value::Int(n)) => n,
+                _ => 0,
+            };
+            if step == 0 {
+                return Err(CompileError {
+                    msg: "range() step must be a nonzero integer constant".to_string(),
+                    token_idx: *token_idx,
+                });
+            }
 
-            let old_break_label = std::mem::replace(&mut ctx.break_label, Some(end_label));
-            let old_continue_label = std::mem::replace(&mut ctx.continue_label, Some(continue_label));
-            compile_block(body, ctx)?;
-            ctx.break_label = old_break_label;
-            ctx.continue_label = old_continue_label;
+            let counter = for_counter_name(*token_idx);
+            let stop_name = for_stop_name(*token_idx);
+            let const_stop = const_value(stop);
+            let stop_is_const = const_stop.is_some();
 
-            let body_label = ctx.label();
-            compile_cond(cond, body_label, end_label, ctx)?;
-            ctx.insns[jump_back_idx] = Insn::Jump(
-                jump_back_idx as isize - ctx.insns.len() as isize);
+            if !stop_is_const {
+                ctx.emit_pop_var(&stop_name);
+                ctx.emit(Insn::PushConst(Value::None));
+            }
+            ctx.emit_pop_var(&counter);
+            ctx.emit(Insn::PushConst(Value::None));
+
+            let cond = Expr::BinaryOp {
+                op: if step > 0 { "<" } else { ">" },
+                op_token_idx: *token_idx,
+                left: Box::new(Expr::Var { name: counter.clone(), token_idx: *token_idx }),
+                right: Box::new(match const_stop {
+                    Some(value) => Expr::Const { value, token_idx: *token_idx },
+                    None => Expr::Var { name: stop_name.clone(), token_idx: *token_idx },
+                }),
+            };
+            let prologue = [
+                Stmt::Assign {
+                    token_idx: *token_idx,
+                    left: var.clone(),
+                    right: Expr::Var { name: counter.clone(), token_idx: *token_idx },
+                    op: None,
+                },
+                Stmt::Assign {
+                    token_idx: *token_idx,
+                    left: counter.clone(),
+                    right: Expr::Const { value: Value::Int(step), token_idx: *token_idx },
+                    op: Some("+"),
+                },
+            ];
+            compile_loop(&cond, &prologue, body, ctx)?;
+
+            if !stop_is_const {
+                ctx.emit_pop_var(&stop_name);
+                compile_expr(stop, ctx)?;
+            }
+            ctx.emit_pop_var(&counter);
+            compile_expr(start, ctx)?;
         }
         Stmt::Break { token_idx } => {
             let break_label = ctx.break_label.ok_or_else(|| {
@@ -625,18 +1333,64 @@ fn compile_stmt(stmt: &Stmt, ctx: &mut Ctx) -> Result<(), CompileError> {
             ctx.emit_jump(0);
             match expr {
                 Some(expr) => compile_expr(expr, ctx)?,
-                None => ctx.insns.push(Insn::PushConst(Value::None)),
+                None => ctx.emit(Insn::PushConst(Value::None)),
             }
         }
         Stmt::Global { token_idx, .. } => return Err(CompileError {
             msg: "global is not allowed here".to_string(),
             token_idx: *token_idx,
         }),
-        _ => todo!(),
+        Stmt::Def { token_idx, .. } => return Err(CompileError {
+            msg: "nested function definitions are not supported".to_string(),
+            token_idx: *token_idx,
+        }),
+        Stmt::Import { token_idx, .. } |
+        Stmt::FromImport { token_idx, .. } => return Err(CompileError {
+            msg: "import is only supported at the top level".to_string(),
+            token_idx: *token_idx,
+        }),
     }
     Ok(())
 }
 
+// Top-level import, after the module has run.
+// 'from module import x' copies the variable, imported functions
+// are resolved at compile time.
+fn compile_import(stmt: &Stmt, m_scope: &Scope, ctx: &mut Ctx) -> Result<(), CompileError> {
+    if let Stmt::FromImport { token_idx, names, .. } = stmt {
+        ctx.at(Some(*token_idx), |ctx| {
+            for name in names.iter().rev() {
+                if !ctx.scope.imported_functions.contains_key(name) {
+                    ctx.emit(Insn::PopGlobal(ctx.scope.global_name(name)));
+                    ctx.emit(Insn::PushGlobal(m_scope.global_name(name)));
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+// 'continue' jumps to the condition check, so the prologue runs on every iteration.
+fn compile_loop(cond: &Expr, prologue: &[Stmt], body: &[Stmt], ctx: &mut Ctx) -> Result<(), CompileError> {
+    let end_label = ctx.label();
+    let jump_back_idx = ctx.insns.len();
+    ctx.emit(Insn::Jump(0));
+    let continue_label = ctx.label();
+
+    let old_break_label = ctx.break_label.replace(end_label);
+    let old_continue_label = ctx.continue_label.replace(continue_label);
+    compile_block(body, ctx)?;
+    compile_block(prologue, ctx)?;
+    ctx.break_label = old_break_label;
+    ctx.continue_label = old_continue_label;
+
+    let body_label = ctx.label();
+    compile_cond(cond, body_label, end_label, ctx)?;
+    ctx.insns[jump_back_idx] = Insn::Jump(
+        jump_back_idx as isize - ctx.insns.len() as isize);
+    Ok(())
+}
+
 fn compile_block(block: &[Stmt], ctx: &mut Ctx) -> Result<(), CompileError> {
     for stmt in block.iter().rev() {
         compile_stmt(stmt, ctx)?;
@@ -654,10 +1408,129 @@ pub mod tests {
         CompiledProgram::new(&mut lfs)
     }
 
+    // Only checks the first line, the one with the location and the message.
     fn expect_error(filename: &str, text: &str, error: &str) {
         let mut lfs = LoadedFiles::new(&[(filename, text)]);
         let e = CompiledProgram::new(&mut lfs).err().unwrap();
-        assert_eq!(lfs.render_error(e), error);
+        assert_eq!(lfs.render_error(e).lines().next().unwrap(), error);
+    }
+
+    #[test]
+    fn carets() {
+        let mut lfs = LoadedFiles::new(&[("a.py", "x = 1\nif y @ 2:\n    pass\n")]);
+        let e = CompiledProgram::new(&mut lfs).err().unwrap();
+        assert_eq!(lfs.render_error(e), "\
+a.py:2:6  lexical error: unrecognized token
+    if y @ 2:
+         ^");
+
+        let mut lfs = LoadedFiles::new(&[("a.py", "x =\tg(1)\n")]);
+        let e = CompiledProgram::new(&mut lfs).err().unwrap();
+        assert_eq!(lfs.render_error(e), "\
+a.py:1:6  function \"g\" not defined
+    x =\tg(1)
+       \t ^");
+
+        let mut lfs = LoadedFiles::new(&[("a.py", "y, 3 = (1, 2)\n")]);
+        let e = CompiledProgram::new(&mut lfs).err().unwrap();
+        assert_eq!(lfs.render_error(e), "\
+a.py:1:4  syntax error: can only unpack to variables
+    y, 3 = (1, 2)
+       ^");
+
+        let mut lfs = LoadedFiles::new(&[("a.py", "x = 'a'\ny = x - 1")]);
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
+        let mut state = super::super::vm::State::new();
+        let e = loop {
+            if let Err(e) = state.step(&cp, &mut vec![]) {
+                break e;
+            }
+        };
+        assert_eq!(lfs.render_error(e), "\
+a.py:2:7  runtime error: can't subtract \"a\" and 1
+    y = x - 1
+          ^");
+    }
+
+    #[test]
+    fn unsupported() {
+        expect_error(
+            "a.py", "
+            def f():
+                def g():
+                    pass
+            ",
+            "a.py:3:17  nested function definitions are not supported");
+        expect_error(
+            "a.py",
+            "x = str(1, 2)",
+            r#"a.py:1:8  function "str" takes 1 arguments (2 given)"#);
+        expect_error(
+            "a.py",
+            "x = len()",
+            r#"a.py:1:8  function "len" takes 1 arguments (0 given)"#);
+        expect_error(
+            "a.py",
+            "_output()",
+            r#"a.py:1:8  function "_output" takes 1 arguments (0 given)"#);
+        expect_error(
+            "a.py",
+            "x = _input(1)",
+            r#"a.py:1:11  function "_input" takes 0 arguments (1 given)"#);
+        expect_error(
+            "a.py",
+            "_forget(x + 1)",
+            "a.py:1:8  _forget() argument must be a variable");
+        expect_error(
+            "a.py",
+            "x = (1)(2)",
+            "a.py:1:8  calling expr that is not a function");
+        expect_error(
+            "a.py",
+            "x = 99999999999",
+            "a.py:1:5  syntax error: integer literal is too large");
+        expect_error(
+            "a.py",
+            "f() = 1",
+            "a.py:1:5  syntax error: can only assign to variables");
+        expect_error(
+            "a.py",
+            "a, f() = 1, 2",
+            "a.py:1:5  syntax error: can only unpack to variables");
+        expect_error(
+            "a.py",
+            "a, b += 1, 2",
+            "a.py:1:6  syntax error: can only assign to variables");
+    }
+
+    fn expect_import_error(modules: &[(&str, &str)], text: &str, error: &str) {
+        let mut lfs = LoadedFiles::new(&[("a.py", text)]);
+        for (name, module_text) in modules {
+            lfs.add_module(name, &format!("{}.py", name), module_text);
+        }
+        let e = CompiledProgram::new(&mut lfs).err().unwrap();
+        assert_eq!(lfs.render_error(e).lines().next().unwrap(), error);
+    }
+
+    #[test]
+    fn import_errors() {
+        expect_import_error(&[], "import nav", r#"a.py:1:1  module "nav" not found"#);
+        expect_import_error(&[("m", "import n"), ("n", "from m import f")], "import m",
+            r#"n.py:1:1  circular import of module "m""#);
+        expect_import_error(&[("m", "x = 1")], "from m import y",
+            r#"a.py:1:1  module "m" has no name "y""#);
+        expect_import_error(&[("m", "x = 1")], "import m\ny = m.y",
+            r#"a.py:2:6  module "m" has no variable "y""#);
+        expect_import_error(&[("m", "x = 1")], "import m\nm.f()",
+            r#"a.py:2:4  module "m" has no function "f""#);
+        expect_import_error(&[("m", "def f(x):\n    pass")], "import m\nm.f()",
+            r#"a.py:2:4  function "m.f" takes 1 arguments (0 given)"#);
+        expect_import_error(&[("m", "x = 1")], "y = 1\nz = y.x",
+            "a.py:2:6  attribute access is only supported on imported modules");
+        expect_import_error(&[("m", "x = 1")], "if True:\n    import m",
+            "a.py:2:5  import is only supported at the top level");
+        expect_import_error(&[("m", "def f():\n    h()")], "def g():\n    pass\nimport m",
+            "m.py:2:6  function \"h\" not defined");
     }
 
     #[test]
@@ -670,6 +1543,14 @@ pub mod tests {
             "a.py",
             "(1 + ",
             "a.py:1:6  syntax error: expected expression");
+        expect_error(
+            "a.py",
+            "for i in f(3):\n    pass",
+            "a.py:1:10  syntax error: only 'for ... in range(...)' loops are supported");
+        expect_error(
+            "a.py",
+            "for i in range():\n    pass",
+            "a.py:1:10  syntax error: range() takes 1 to 3 arguments (0 given)");
     }
 
     #[test]
@@ -690,6 +1571,86 @@ pub mod tests {
                 x = 1
             ").unwrap();
         eprintln!("{}", cp);
+
+        expect_error(
+            "a.py",
+            "for i in range(0, 10, n):\n    pass",
+            "a.py:1:1  range() step must be a nonzero integer constant");
+        expect_error(
+            "a.py",
+            "for i in range(0, 10, 0):\n    pass",
+            "a.py:1:1  range() step must be a nonzero integer constant");
+
+        let cp = compile_single_file("example.py", "
+            def f(n):
+                for i in range(n):
+                    for j in range(3):
+                        pass
+            ").unwrap();
+        eprintln!("{}", cp);
+        let cf = &cp.functions[0];
+        assert_eq!(cf.local_names, [
+            "i", "for@8.counter", "for@8.stop",
+            "j", "for@18.counter", "for@18.stop",
+        ]);
+    }
+
+    #[test]
+    fn constant_folding() {
+        fn entry_insns(text: &str) -> Vec<String> {
+            let cp = compile_single_file("a.py", text).unwrap();
+            (0..cp.insns.len()).map(|i| cp.insns[i].display(i, &cp, None)).collect()
+        }
+
+        assert_eq!(entry_insns("x = 0-1 + 3"), ["push 2", "pop global x"]);
+        assert_eq!(entry_insns("x = 'a' + str(1) + ' ' + str(len((1, [2])))"),
+            [r#"push "a1 2""#, "pop global x"]);
+        assert_eq!(entry_insns("x = (1, 2)[-1] * 2 if not 1 > 2 and True else y"),
+            ["push 4", "pop global x"]);
+        assert_eq!(entry_insns("x = -(1 + y)").len(), 5);
+        // errors are left to the runtime
+        assert_eq!(entry_insns("x = 1 / 0"), ["push 1", "push 0", "binop \"/\"", "pop global x"]);
+
+        assert_eq!(entry_insns("
+            if 1 < 2:
+                x = 1
+            else:
+                x = 2
+            while 1 == 2:
+                y = 3
+            z = 3 if 2 + 2 == 5 else y
+            "),
+            ["push 1", "pop global x", "push global y", "pop global z"]);
+        let cp = compile_single_file("a.py", "
+            def f():
+                for i in range(2 * 3):
+                    pass
+            ").unwrap();
+        let cf = &cp.functions[0];
+        assert!((0..cf.insns.len()).all(|i| !cf.insns[i].display(i, &cp, Some(cf)).contains(".stop")));
+
+        // dead code is still checked
+        expect_error("a.py", "if False:\n    f()", r#"a.py:2:6  function "f" not defined"#);
+        expect_error("a.py", "while False:\n    break\nbreak", "a.py:3:1  break outside of loop");
+    }
+
+    #[test]
+    fn ant_io_builtins() {
+        let cp = compile_single_file("a.py", "x = sense('left_ahead', 'foe' + 'marker')").unwrap();
+        assert_eq!(cp.insns[0].display(0, &cp, None), "push <Sense LeftAhead 1 0 FoeMarker>");
+
+        expect_error("a.py", "sense('here', 'fod')",
+            r#"a.py:1:15  unknown sense condition "fod", expected one of "friend", "foe", "friendwithfood", "foewithfood", "food", "rock", "foemarker", "home", "foehome""#);
+        expect_error("a.py", "if True:\n    mark(7)",
+            "a.py:2:10  marker must be an integer from 0 to 5, got 7");
+        expect_error("a.py", "flip(0)",
+            "a.py:1:6  flip() argument must be a positive integer, got 0");
+        expect_error("a.py", "turn(1 + 1)",
+            r#"a.py:1:8  unknown turn direction 2, expected one of "left", "right""#);
+        expect_error("a.py", "move(1)",
+            r#"a.py:1:5  function "move" takes 0 arguments (1 given)"#);
+        expect_error("a.py", "def len(x):\n    pass",
+            r#"a.py:1:1  function "len" shadows a builtin"#);
     }
 
     #[test]
@@ -2,3 +2,4 @@ mod tokenizer;
 mod ast;
 pub mod compiler;
 pub mod vm;
+pub mod ant_io;
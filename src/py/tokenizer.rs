@@ -23,7 +23,7 @@ pub struct Token {
 // First match is used, so '+=' should be before '+', etc.
 const PUNCTUATION: &[&str] = &[
     "(", ")", "[", "]", "{", "}",
-    ":", ",",
+    ":", ",", ".",
     "<=", ">=",
     "<", ">",
     "==", "!=",
@@ -33,9 +33,9 @@ const PUNCTUATION: &[&str] = &[
 ];
 
 const KEYWORDS: &[&str] = &[
-    "def", "global",
+    "def", "global", "import", "from",
     "return", "pass", "break", "continue",
-    "if", "else", "while",
+    "if", "elif", "else", "while", "for", "in",
     "True", "False",
     "None",
     "and", "or", "not",
@@ -1,7 +1,9 @@
 #![allow(dead_code)]  // TODO
 
 use std::collections::BTreeMap;
-use super::compiler::{Insn, CompiledProgram};
+use super::compiler::{Insn, CompiledProgram, AnyError, LoadedFiles, Loc};
+use crate::neurology::Instruction;
+use super::ant_io;
 
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Value {
@@ -9,6 +11,9 @@ pub enum Value {
     Bool(bool),
     Int(i32),
     String(String),
+    Tuple(Vec<Value>),
+    List(Vec<Value>),  // immutable, like Tuple, so that states stay hashable
+    Insn(Instruction),  // made by ant IO builtins like sense()
 }
 
 impl std::fmt::Debug for Value {
@@ -18,15 +23,37 @@ impl std::fmt::Debug for Value {
             Value::Bool(b) => write!(f, "{}", b),
             Value::Int(i) => write!(f, "{}", i),
             Value::String(s) => write!(f, "{:?}", s),
+            Value::Tuple(items) => {
+                write!(f, "(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{:?}", item)?;
+                }
+                if items.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::List(items) => f.debug_list().entries(items).finish(),
+            Value::Insn(insn) => write!(f, "<{}>", insn),
         }
     }
 }
 
+// Result of integer arithmetic, None if it overflowed.
+fn int(x: Option<i32>) -> Result<Value, RuntimeError> {
+    x.map(Value::Int).ok_or_else(|| RuntimeError("integer overflow".to_owned()))
+}
+
 impl Value {
     fn add(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a + *b)),
+            (Value::Int(a), Value::Int(b)) => int(a.checked_add(*b)),
             (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
+            (Value::Tuple(a), Value::Tuple(b)) => Ok(Value::Tuple([&a[..], &b[..]].concat())),
+            (Value::List(a), Value::List(b)) => Ok(Value::List([&a[..], &b[..]].concat())),
             _ => Err(RuntimeError(
                 format!("can't add {:?} and {:?}", self, other)
             )),
@@ -35,7 +62,7 @@ impl Value {
 
     fn sub(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a - *b)),
+            (Value::Int(a), Value::Int(b)) => int(a.checked_sub(*b)),
             _ => Err(RuntimeError(
                 format!("can't subtract {:?} and {:?}", self, other)
             )),
@@ -44,7 +71,7 @@ impl Value {
 
     fn mul(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
-            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a * *b)),
+            (Value::Int(a), Value::Int(b)) => int(a.checked_mul(*b)),
             _ => Err(RuntimeError(
                 format!("can't multiply {:?} and {:?}", self, other)
             )),
@@ -56,7 +83,7 @@ impl Value {
             (Value::Int(a), Value::Int(b)) => if *b == 0 {
                 Err(RuntimeError("division by zero".to_owned()))
             } else {
-                Ok(Value::Int(a.div_euclid(*b)))
+                int(a.checked_div_euclid(*b))
             }
             _ => Err(RuntimeError(
                 format!("can't divide {:?} by {:?}", self, other)
@@ -69,7 +96,7 @@ impl Value {
             (Value::Int(a), Value::Int(b)) => if *b == 0 {
                 Err(RuntimeError("division by zero".to_owned()))
             } else {
-                Ok(Value::Int(a.rem_euclid(*b)))
+                int(a.checked_rem_euclid(*b))
             }
             _ => Err(RuntimeError(
                 format!("can't divide {:?} by {:?}", self, other)
@@ -77,6 +104,51 @@ impl Value {
         }
     }
 
+    fn neg(&self) -> Result<Value, RuntimeError> {
+        match self {
+            Value::Int(a) => int(a.checked_neg()),
+            _ => Err(RuntimeError(
+                format!("can't negate {:?}", self)
+            )),
+        }
+    }
+
+    fn items(&self) -> Option<&[Value]> {
+        match self {
+            Value::Tuple(items) | Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn index(&self, idx: &Value) -> Result<Value, RuntimeError> {
+        let items = self.items().ok_or_else(|| RuntimeError(
+            format!("can't index {:?}", self)
+        ))?;
+        match idx {
+            &Value::Int(i) => {
+                let j = if i < 0 { i + items.len() as i32 } else { i };
+                if 0 <= j && (j as usize) < items.len() {
+                    Ok(items[j as usize].clone())
+                } else {
+                    Err(RuntimeError(format!("index {} out of range", i)))
+                }
+            }
+            _ => Err(RuntimeError(
+                format!("can't use {:?} as index", idx)
+            )),
+        }
+    }
+
+    fn len(&self) -> Result<Value, RuntimeError> {
+        match self {
+            Value::Tuple(items) | Value::List(items) => Ok(Value::Int(items.len() as i32)),
+            Value::String(s) => Ok(Value::Int(s.chars().count() as i32)),
+            _ => Err(RuntimeError(
+                format!("can't call len() on {:?}", self)
+            )),
+        }
+    }
+
     fn str(&self) -> Result<Value, RuntimeError> {
         match self {
             Value::Int(a) => Ok(Value::String(format!("{}", a))),
@@ -87,6 +159,42 @@ impl Value {
     }
 }
 
+impl Value {
+    // Also used by the compiler for constant folding.
+    pub fn unary_op(&self, op: &str) -> Result<Value, RuntimeError> {
+        match op {
+            "str" => self.str(),
+            "-" => self.neg(),
+            "len" => self.len(),
+            _ => Err(RuntimeError(format!("unknown operator {:?}", op))),
+        }
+    }
+
+    pub fn binary_op(&self, op: &str, other: &Value) -> Result<Value, RuntimeError> {
+        Ok(match op {
+            "+" => self.add(other)?,
+            "-" => self.sub(other)?,
+            "*" => self.mul(other)?,
+            "/" => self.div(other)?,
+            "%" => self.rem(other)?,
+            "[]" => self.index(other)?,
+            "==" => Value::Bool(self.eq(other)),
+            "!=" => Value::Bool(!self.eq(other)),
+            "<" => Value::Bool(self.cmp(other)? == std::cmp::Ordering::Less),
+            "<=" => Value::Bool(self.cmp(other)? != std::cmp::Ordering::Greater),
+            ">" => Value::Bool(self.cmp(other)? == std::cmp::Ordering::Greater),
+            ">=" => Value::Bool(self.cmp(other)? != std::cmp::Ordering::Less),
+            _ => return Err(RuntimeError(format!("unknown operator {:?}", op))),
+        })
+    }
+
+    fn cmp(&self, other: &Value) -> Result<std::cmp::Ordering, RuntimeError> {
+        self.partial_cmp(other).ok_or_else(|| RuntimeError(
+            format!("can't compare {:?} and {:?}", self, other)
+        ))
+    }
+}
+
 impl std::cmp::PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
@@ -135,6 +243,64 @@ impl State {
         }
     }
 
+    // Call stack with source locations and local variables, innermost first, like
+    //   prelude.py:55 move() < ant.py:12 go(n=3) < ant.py:20 <module>
+    // Locations are those of the last executed instruction in each frame.
+    pub fn backtrace(&self, cp: &CompiledProgram, lfs: &LoadedFiles) -> String {
+        let mut frames = vec![];
+        for frame in self.call_stack.iter().rev() {
+            let loc = lfs.render_file_line(cp.insn_loc(frame.fn_idx, frame.ip.saturating_sub(1)));
+            let f = match frame.fn_idx {
+                None => "<module>".to_string(),
+                Some(fn_idx) => {
+                    let cf = &cp.functions[fn_idx];
+                    let locals: Vec<String> = frame.locals.iter().enumerate()
+                        .filter_map(|(i, v)| v.as_ref().map(|v| {
+                            format!("{}={:?}", cf.arg_or_local_name_by_idx(i).1, v)
+                        }))
+                        .collect();
+                    format!("{}({})", cf.name, locals.join(", "))
+                }
+            };
+            frames.push(format!("{} {}", loc, f));
+        }
+        frames.join(" < ")
+    }
+
+    // Location of the instruction the next step executes,
+    // None when returning from a function or terminating.
+    pub fn next_loc(&self, cp: &CompiledProgram) -> Option<Loc> {
+        let frame = self.call_stack.last().unwrap();
+        let num_insns = match frame.fn_idx {
+            None => cp.insns.len(),
+            Some(fn_idx) => cp.functions[fn_idx].insns.len(),
+        };
+        if frame.ip < num_insns {
+            Some(cp.insn_loc(frame.fn_idx, frame.ip))
+        } else {
+            None
+        }
+    }
+
+    // Globals and locals of all frames, locals named like 'f.x'.
+    pub fn variables(&self, cp: &CompiledProgram) -> Vec<(String, &Value)> {
+        let mut res: Vec<(String, &Value)> = self.globals.iter()
+            .map(|(name, v)| (name.clone(), v))
+            .collect();
+        for frame in &self.call_stack {
+            if let Some(fn_idx) = frame.fn_idx {
+                let cf = &cp.functions[fn_idx];
+                for (i, v) in frame.locals.iter().enumerate() {
+                    if let Some(v) = v {
+                        let name = cf.arg_or_local_name_by_idx(i).1;
+                        res.push((format!("{}.{}", cf.name, name), v));
+                    }
+                }
+            }
+        }
+        res
+    }
+
     pub fn give_input(&mut self, input: Value) {
         assert!(self.input.is_none());
         self.input = Some(input);
@@ -144,6 +310,17 @@ impl State {
         &mut self,
         cp: &CompiledProgram,
         output: &mut Vec<Value>,
+    ) -> Result<Status, AnyError> {
+        let frame = self.call_stack.last().unwrap();
+        let (fn_idx, ip) = (frame.fn_idx, frame.ip);
+        self.step_impl(cp, output).map_err(
+            |RuntimeError(msg)| cp.runtime_error(msg, fn_idx, ip))
+    }
+
+    fn step_impl(
+        &mut self,
+        cp: &CompiledProgram,
+        output: &mut Vec<Value>,
     ) -> Result<Status, RuntimeError> {
         let frame = self.call_stack.last_mut().unwrap();
         let insns = match frame.fn_idx {
@@ -170,33 +347,18 @@ impl State {
                     self.value_stack.push(c.clone()),
                 &Insn::UnOp(op) => {
                     let a = self.value_stack.pop().unwrap();
-                    let res = match op {
-                        "str" => a.str()?,
-                        _ => panic!("{:?}", op),
-                    };
-                    self.value_stack.push(res);
+                    self.value_stack.push(a.unary_op(op)?);
                 }
                 &Insn::BinOp(op) => {
                     let b = self.value_stack.pop().unwrap();
                     let a = self.value_stack.pop().unwrap();
-                    let res = match op {
-                        "+" => a.add(&b)?,
-                        "-" => a.sub(&b)?,
-                        "*" => a.mul(&b)?,
-                        "/" => a.div(&b)?,
-                        "%" => a.rem(&b)?,
-                        "==" => Value::Bool(a.eq(&b)),
-                        "!=" => Value::Bool(!a.eq(&b)),
-                        "<" => Value::Bool(a.partial_cmp(&b).expect("TODO") == std::cmp::Ordering::Less),
-                        "<=" => Value::Bool(a.partial_cmp(&b).expect("TODO") != std::cmp::Ordering::Greater),
-                        ">" => Value::Bool(a.partial_cmp(&b).expect("TODO") == std::cmp::Ordering::Greater),
-                        ">=" => Value::Bool(a.partial_cmp(&b).expect("TODO") != std::cmp::Ordering::Less),
-                        _ => panic!("{:?}", op),
-                    };
-                    self.value_stack.push(res);
+                    self.value_stack.push(a.binary_op(op, &b)?);
                 }
-                Insn::PushGlobal(name) => {
-                    self.value_stack.push(self.globals[name].clone());
+                Insn::PushGlobal(name) => match self.globals.get(name) {
+                    Some(v) => self.value_stack.push(v.clone()),
+                    None => return Err(RuntimeError(
+                        format!("name {:?} is not defined", name)
+                    )),
                 }
                 Insn::PopGlobal(name) => {
                     let v = self.value_stack.pop().unwrap();
@@ -216,6 +378,31 @@ impl State {
                     let v = self.value_stack.pop().unwrap();
                     frame.locals[i] = Some(v);
                 }
+                Insn::ForgetGlobal(name) => {
+                    self.globals.remove(name);
+                }
+                &Insn::ForgetLocal(i) => {
+                    frame.locals[i] = None;
+                }
+                &Insn::BuildTuple(n) | &Insn::BuildList(n) => {
+                    let items = self.value_stack.split_off(self.value_stack.len() - n);
+                    self.value_stack.push(match insn {
+                        Insn::BuildTuple(_) => Value::Tuple(items),
+                        _ => Value::List(items),
+                    });
+                }
+                &Insn::Unpack(n) => {
+                    let v = self.value_stack.pop().unwrap();
+                    let items = v.items().ok_or_else(|| RuntimeError(
+                        format!("can't unpack {:?}", v)
+                    ))?;
+                    if items.len() != n {
+                        return Err(RuntimeError(
+                            format!("can't unpack {} values into {} variables", items.len(), n)
+                        ));
+                    }
+                    self.value_stack.extend(items.iter().rev().cloned());
+                }
                 Insn::Jump(delta) => {
                     frame.ip = (frame.ip as isize + delta) as usize;
                 }
@@ -244,6 +431,13 @@ impl State {
                         fn_idx: Some(f_idx),
                     });
                 }
+                &Insn::AntInsn(name) => {
+                    let (_, num_args, _) = ant_io::builtin(name).unwrap();
+                    let args = self.value_stack.split_off(self.value_stack.len() - num_args);
+                    let insn = ant_io::make_insn(name, &args).map_err(
+                        |(_, msg)| RuntimeError(format!("{}(): {}", name, msg)))?;
+                    self.value_stack.push(Value::Insn(insn));
+                }
                 Insn::Output => {
                     let v = self.value_stack.pop().unwrap();
                     output.push(v);
@@ -271,15 +465,21 @@ impl State {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::compiler::LoadedFiles;
     use super::super::compiler::tests::compile_single_file;
 
-    fn run_to_termination(filename: &str, text: &str) -> Result<State, RuntimeError> {
-        let cp = compile_single_file(filename, text).unwrap();
+    // Returns rendered error on failure.
+    fn run_to_termination(filename: &str, text: &str) -> Result<State, String> {
+        run_files(LoadedFiles::new(&[(filename, text)]))
+    }
+
+    fn run_files(mut lfs: LoadedFiles) -> Result<State, String> {
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
         eprintln!("{}", cp);
         let mut state = State::new();
         let mut output = vec![];
         loop {
-            match state.step(&cp, &mut output)? {
+            match state.step(&cp, &mut output).map_err(|e| lfs.render_error(e))? {
                 Status::Terminated => return Ok(state),
                 Status::Running => {}
                 Status::BlockedOnInput => panic!(),
@@ -289,12 +489,12 @@ mod tests {
 
     fn expect_runtime_error(filename: &str, text: &str, expected: &str) {
         let e = run_to_termination(filename, text).err().unwrap();
-        assert_eq!(e.0, expected);
+        assert_eq!(e.lines().next().unwrap(), expected);
     }
 
     #[test]
     fn smoke() {
-        expect_runtime_error("a.py", "1 + True", "can't add 1 and true");
+        expect_runtime_error("a.py", "1 + True", "a.py:1:3  runtime error: can't add 1 and true");
 
         let state = run_to_termination("a.py", "g = 1 + 2").unwrap();
         assert_eq!(state.globals["g"], Value::Int(3));
@@ -350,6 +550,326 @@ mod tests {
         assert_eq!(state.globals["c_5_2"], Value::Int(10));
     }
 
+    #[test]
+    fn elif() {
+        let state = run_to_termination("a.py", "
+            def sign(x):
+                if x < 0:
+                    return -1
+                elif x == 0:
+                    return 0
+                else:
+                    return 1
+
+            a = sign(-5)
+            b = sign(0)
+            c = sign(7)
+            ").unwrap();
+        assert_eq!(state.globals["a"], Value::Int(-1));
+        assert_eq!(state.globals["b"], Value::Int(0));
+        assert_eq!(state.globals["c"], Value::Int(1));
+    }
+
+    #[test]
+    fn for_range() {
+        let state = run_to_termination("a.py", "
+            def triangle(n):
+                s = 0
+                for i in range(1, n + 1):
+                    s += i
+                return s
+
+            def countdown():
+                s = ''
+                for i in range(5, 0, -2):
+                    s += str(i)
+                return s
+
+            t = triangle(4)
+            d = countdown()
+
+            total = 0
+            for i in range(10):
+                if i == 2:
+                    continue
+                if i == 5:
+                    break
+                i = 100
+                total += 1
+            for j in range(0):
+                total = 1000
+            ").unwrap();
+        assert_eq!(state.globals["t"], Value::Int(10));
+        assert_eq!(state.globals["d"], Value::String("531".to_string()));
+        assert_eq!(state.globals["total"], Value::Int(4));
+        assert_eq!(state.globals["i"], Value::Int(5));
+        assert!(!state.globals.contains_key("j"));
+        // hidden loop variables are reset after the loop
+        assert!(state.globals.iter()
+            .filter(|(name, _)| name.starts_with("for@"))
+            .all(|(_, v)| *v == Value::None));
+    }
+
+    #[test]
+    fn ternary() {
+        let state = run_to_termination("a.py", "
+            def abs(x):
+                return x if x >= 0 else -x
+
+            a = abs(-3)
+            b = abs(4)
+            c = 1 if False else 2 if True else 3
+            ").unwrap();
+        assert_eq!(state.globals["a"], Value::Int(3));
+        assert_eq!(state.globals["b"], Value::Int(4));
+        assert_eq!(state.globals["c"], Value::Int(2));
+    }
+
+    #[test]
+    fn tuples_and_lists() {
+        expect_runtime_error("a.py", "x = (1, 2)[2]",
+            "a.py:1:11  runtime error: index 2 out of range");
+        expect_runtime_error("a.py", "x = 1[0]",
+            "a.py:1:6  runtime error: can't index 1");
+        expect_runtime_error("a.py", "a, b = (1, 2, 3)",
+            "a.py:1:6  runtime error: can't unpack 3 values into 2 variables");
+        expect_runtime_error("a.py", "a, b = 1",
+            "a.py:1:6  runtime error: can't unpack 1");
+        expect_runtime_error("a.py", "x = [1] + (2,)",
+            "a.py:1:9  runtime error: can't add [1] and (2,)");
+
+        let state = run_to_termination("a.py", "
+            def divmod(a, b):
+                return a / b, a % b
+
+            def last(xs):
+                return xs[len(xs) - 1]
+
+            q, r = divmod(17, 5)
+            path = []
+            for i in range(3):
+                path = path + [i * 10]
+            first = path[0]
+            end = path[-1]
+            l = last(path)
+            n = len(path)
+            t = ()
+            u = (1,) + (2, 3)
+            a, b = 'a', 'b'
+            a, b = b, a
+            ").unwrap();
+        assert_eq!(state.globals["q"], Value::Int(3));
+        assert_eq!(state.globals["r"], Value::Int(2));
+        assert_eq!(state.globals["path"], Value::List(vec![Value::Int(0), Value::Int(10), Value::Int(20)]));
+        assert_eq!(state.globals["first"], Value::Int(0));
+        assert_eq!(state.globals["end"], Value::Int(20));
+        assert_eq!(state.globals["l"], Value::Int(20));
+        assert_eq!(state.globals["n"], Value::Int(3));
+        assert_eq!(state.globals["t"], Value::Tuple(vec![]));
+        assert_eq!(format!("{:?}", state.globals["u"]), "(1, 2, 3)");
+        assert_eq!(state.globals["a"], Value::String("b".to_string()));
+        assert_eq!(state.globals["b"], Value::String("a".to_string()));
+    }
+
+    #[test]
+    fn type_errors() {
+        // the parser never produces these, but they're errors rather than panics
+        assert!(Value::Int(1).unary_op("~").is_err());
+        assert!(Value::Int(1).binary_op("@", &Value::Int(2)).is_err());
+        expect_runtime_error("a.py", "x = 1 < 'a'",
+            "a.py:1:7  runtime error: can't compare 1 and \"a\"");
+        expect_runtime_error("a.py", "x = None >= None",
+            "a.py:1:10  runtime error: can't compare None and None");
+        expect_runtime_error("a.py", "x = y",
+            "a.py:1:5  runtime error: name \"y\" is not defined");
+        expect_runtime_error("a.py", "x = -'a'",
+            "a.py:1:5  runtime error: can't negate \"a\"");
+        expect_runtime_error("a.py", "x = str(None)",
+            "a.py:1:8  runtime error: can't call str() on None");
+        expect_runtime_error("a.py", "x = len(1)",
+            "a.py:1:8  runtime error: can't call len() on 1");
+        expect_runtime_error("a.py", "x = 1 / 0",
+            "a.py:1:7  runtime error: division by zero");
+        expect_runtime_error("a.py", "x = -2147483647 - 1\ny = -x",
+            "a.py:2:5  runtime error: integer overflow");
+        expect_runtime_error("a.py", "x = 2147483647\nx += 1",
+            "a.py:2:3  runtime error: integer overflow");
+        expect_runtime_error("a.py", "x = -2147483647 - 1\ny = x / -1",
+            "a.py:2:7  runtime error: integer overflow");
+        expect_runtime_error("a.py", "x = 1\nx += 'a'",
+            "a.py:2:3  runtime error: can't add 1 and \"a\"");
+        expect_runtime_error("a.py", "x = 1\nif x:\n    pass",
+            "a.py:2:4  runtime error: can't use value 1 in boolean context");
+        expect_runtime_error("a.py", "x = 1 if 2 else 3",
+            "a.py:1:10  runtime error: can't use value 2 in boolean context");
+        expect_runtime_error("a.py", "for i in range('a'):\n    pass",
+            "a.py:1:1  runtime error: can't compare 0 and \"a\"");
+        expect_runtime_error("a.py", "
+            def f():
+                if False:
+                    x = 1
+                return x
+            f()",
+            "a.py:5:24  runtime error: local x referenced before assignment");
+    }
+
+    #[test]
+    fn backtrace() {
+        let text = "
+            def f(x):
+                y = x + 1
+                if False:
+                    z = 0
+                _output(y)
+                return _input()
+
+            def g():
+                return f(41)
+
+            g()
+            ";
+        let mut lfs = LoadedFiles::new(&[("a.py", text)]);
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
+        let mut state = State::new();
+        let mut output = vec![];
+        while output.is_empty() {
+            state.step(&cp, &mut output).unwrap();
+        }
+        assert_eq!(state.backtrace(&cp, &lfs),
+            "a.py:6 f(x=41, y=42) < a.py:10 g() < a.py:12 <module>");
+    }
+
+    #[test]
+    fn modules() {
+        let mut lfs = LoadedFiles::new(&[
+            ("prelude.py", "
+                def inc(x):
+                    return x + 1
+                BASE = 10
+            "),
+            ("a.py", "
+                x = 'main'
+                import m
+                from n import COUNT, twice
+                y = m.f(1)
+                z = twice(COUNT)
+                m.set_x(5)
+                w = m.x
+            "),
+        ]);
+        lfs.add_module("m", "m.py", "
+            from n import twice
+            x = 0
+            def f(a):
+                return twice(inc(a)) + x + BASE
+            def set_x(v):
+                global x
+                x = v
+        ");
+        lfs.add_module("n", "n.py", "
+            COUNT = 3
+            def twice(a):
+                return 2 * a
+        ");
+        let state = run_files(lfs).unwrap();
+        assert_eq!(state.globals["x"], Value::String("main".to_string()));
+        assert_eq!(state.globals["y"], Value::Int(14));
+        assert_eq!(state.globals["z"], Value::Int(6));
+        assert_eq!(state.globals["m.x"], Value::Int(5));
+        assert_eq!(state.globals["w"], Value::Int(5));
+        assert_eq!(state.globals["n.COUNT"], Value::Int(3));
+        assert_eq!(state.globals["COUNT"], Value::Int(3));
+
+        // A module runs once, at its first import.
+        let mut lfs = LoadedFiles::new(&[("a.py", "
+            def note(s):
+                global log
+                log += (s,)
+            log = ()
+            import m
+            note('a')
+            import m
+            from m import X
+        ")]);
+        lfs.add_module("m", "m.py", "
+            note('m')
+            X = len(log)
+        ");
+        let state = run_files(lfs).unwrap();
+        let log = |items: &[&str]| Value::Tuple(items.iter().map(|s| Value::String(s.to_string())).collect());
+        assert_eq!(state.globals["log"], log(&["m", "a"]));
+        assert_eq!(state.globals["X"], Value::Int(1));
+    }
+
+    #[test]
+    fn forget() {
+        expect_runtime_error("a.py", "x = 1\n_forget(x)\ny = x",
+            "a.py:3:5  runtime error: name \"x\" is not defined");
+        expect_runtime_error("a.py", "
+            def f(a):
+                b = a
+                _forget(b)
+                return b
+            f(1)",
+            "a.py:5:24  runtime error: local b referenced before assignment");
+
+        let state = run_to_termination("a.py", "
+            def f(a):
+                _forget(a)
+                a = 2
+                return a
+            x = 1
+            y = 1
+            _forget(x)
+            _forget(z)
+            y = f(y)
+            ").unwrap();
+        assert!(!state.globals.contains_key("x"));
+        assert_eq!(state.globals["y"], Value::Int(2));
+    }
+
+    #[test]
+    fn ant_io() {
+        let mut lfs = LoadedFiles::new(&[("a.py", "
+            dirs = ['here', 'ahead']
+            for i in range(2):
+                if sense(dirs[i], 'food'):
+                    mark(i + 4)
+            moved = move()
+            ")]);
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
+        let mut state = State::new();
+        let mut output = vec![];
+        let mut next_input = [1, 0, 0, 0].iter();
+        loop {
+            match state.step(&cp, &mut output).unwrap() {
+                Status::Terminated => break,
+                Status::Running => {}
+                Status::BlockedOnInput =>
+                    state.give_input(Value::Int(*next_input.next().unwrap())),
+            }
+        }
+        let output: Vec<String> = output.iter().map(|v| format!("{:?}", v)).collect();
+        assert_eq!(output, [
+            "<Sense Here 1 0 Food>",
+            "<Mark 4 0>",
+            "<Sense Ahead 1 0 Food>",
+            "<Move 1 0>",
+        ]);
+        assert_eq!(state.globals["moved"], Value::Bool(false));
+
+        expect_runtime_error("a.py", "m = 3 + 4\nmark(m)",
+            "a.py:2:5  runtime error: mark(): marker must be an integer from 0 to 5, got 7");
+
+        // a helper written before move() was a builtin
+        let state = run_to_termination("a.py", "
+            def move(n):
+                return n + 1
+            x = move(2)
+            ").unwrap();
+        assert_eq!(state.globals["x"], Value::Int(3));
+    }
+
     #[test]
     fn io() {
         let cp = compile_single_file("a.py", "
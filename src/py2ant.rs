@@ -1,31 +1,75 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::convert::TryInto;
-use std::collections::HashMap;
-use crate::neurology::{State, Instruction};
-use crate::neurology;
+use std::collections::{HashMap, HashSet};
+use crate::neurology::{State, Instruction, MAX_STATES};
+use crate::magic::AnnotatedBrain;
 use crate::py::compiler::*;
 use crate::py::vm::{Status, Value};
 use crate::py::vm;
 
-struct BrainWithSymbols {
-    insns: Vec<Instruction>,
+// What the ant program output before asking for input, loc is
+// where to report it if that's not a single instruction.
+pub fn outputs_to_insn(outputs: &[Value], loc: Loc, cp: &CompiledProgram)
+-> Result<Instruction, AnyError> {
+    match outputs {
+        [Value::Insn(insn)] => Ok(*insn),
+        // fallback for _output('sense ahead 1 0 food') and the like
+        [Value::String(cmd)] => Instruction::try_parse(cmd)
+            .map_err(|e| cp.runtime_error_at(e, loc)),
+        _ => Err(cp.runtime_error_at(
+            format!("expected a single instruction before _input(), got {:?}", outputs), loc)),
+    }
 }
 
-fn outputs_to_insn(outputs: &[Value]) -> Instruction {
-    match outputs {
-        [Value::String(cmd)] => Instruction::parse(cmd),
-        _ => panic!("{:?}", outputs),
+// By convention ALL_CAPS globals are constants like HERE or FOOD,
+// module globals are prefixed with the module name.
+pub fn is_constant(name: &str) -> bool {
+    let short_name = name.rsplit('.').next().unwrap();
+    !short_name.chars().any(|c| c.is_lowercase())
+}
+
+// Where the last output was emitted (see vm::State::backtrace),
+// followed by the non-constant globals.
+fn describe_output(vm_state: &vm::State, lfs: &LoadedFiles, cp: &CompiledProgram) -> String {
+    use std::fmt::Write;
+    let mut comment = vm_state.backtrace(cp, lfs);
+    for (name, value) in &vm_state.globals {
+        if !is_constant(name) {
+            write!(comment, ", {}={:?}", name, value).unwrap();
+        }
     }
+    comment
 }
 
-fn run_to_input(vm_state: &mut vm::State, cp: &CompiledProgram) -> Vec<Value> {
+// Returns the instruction output before blocking on input
+// and the description of where it was emitted.
+fn run_to_input(vm_state: &mut vm::State, lfs: &LoadedFiles, cp: &CompiledProgram)
+-> Result<(Instruction, String), AnyError> {
     let mut outputs = vec![];
+    let mut comment = String::new();
+    // of the last executed instruction and of the last _output() call
+    let mut last_loc = None;
+    let mut output_loc = None;
     loop {
-        let res = vm_state.step(cp, &mut outputs).unwrap();
+        let num_outputs = outputs.len();
+        let loc = vm_state.next_loc(cp);
+        let res = vm_state.step(cp, &mut outputs)?;
+        last_loc = loc.or(last_loc);
+        if outputs.len() > num_outputs {
+            comment = describe_output(vm_state, lfs, cp);
+            output_loc = loc;
+        }
         match res {
-            Status::Terminated => panic!("ant should be an infinite loop"),
+            Status::Terminated => {
+                let loc = last_loc.unwrap_or_else(|| lfs.end_loc());
+                return Err(cp.runtime_error_at("ant program ended".to_string(), loc));
+            }
             Status::Running => continue,
-            Status::BlockedOnInput => return outputs,
+            Status::BlockedOnInput => {
+                // at the _input() call if nothing was output
+                let loc = output_loc.or(loc).unwrap();
+                return Ok((outputs_to_insn(&outputs, loc, cp)?, comment));
+            }
         }
     }
 }
@@ -61,18 +105,59 @@ impl Node {
     }
 }
 
-fn unroll_dfa(_lfs: &LoadedFiles, cp: &CompiledProgram) -> BrainWithSymbols {
+enum UnrollError {
+    Runtime(AnyError),
+    // Report on the variables that contribute most to the explosion.
+    TooManyStates(String),
+}
+
+impl From<AnyError> for UnrollError {
+    fn from(e: AnyError) -> Self {
+        UnrollError::Runtime(e)
+    }
+}
+
+// Variables sorted by how many different values they take
+// across the explored VM states.
+fn explosion_report<'a>(
+    vm_states: impl Iterator<Item=&'a vm::State>,
+    cp: &CompiledProgram,
+) -> String {
+    use std::fmt::Write;
+    let mut values: HashMap<String, HashSet<&Value>> = HashMap::new();
+    let mut num_states = 0;
+    for vm_state in vm_states {
+        num_states += 1;
+        for (name, v) in vm_state.variables(cp) {
+            values.entry(name).or_default().insert(v);
+        }
+    }
+    let mut values: Vec<_> = values.into_iter().collect();
+    values.sort_by(|(n1, vs1), (n2, vs2)| vs2.len().cmp(&vs1.len()).then(n1.cmp(n2)));
+
+    let mut report = format!(
+        "too many states (explored {} VM states)\n\
+         variables with most distinct values:\n", num_states);
+    // variables with a single value don't multiply states
+    for (name, vs) in values.iter().filter(|(_, vs)| vs.len() > 1).take(10) {
+        writeln!(report, "{:>8}  {}", vs.len(), name).unwrap();
+    }
+    report.push_str("hint: use _forget(var) once a variable is no longer needed");
+    report
+}
+
+fn unroll_dfa(lfs: &LoadedFiles, cp: &CompiledProgram, max_states: usize)
+-> Result<AnnotatedBrain, UnrollError> {
     let mut vm_state = vm::State::new();
     let mut nodes = vec![];
     #[allow(clippy::type_complexity)]
     let mut idx_map: HashMap<(vm::State, Instruction), usize> = HashMap::new();
 
-    let outputs = run_to_input(&mut vm_state, cp);
-    let insn = outputs_to_insn(&outputs);
+    let (insn, comment) = run_to_input(&mut vm_state, lfs, cp)?;
 
     let idx = nodes.len();
     idx_map.insert((vm_state.clone(), insn), idx);
-    nodes.push(Node::new(insn, format!("{:?}", vm_state.globals)));
+    nodes.push(Node::new(insn, comment));
 
     let mut worklist = vec![(idx, vm_state, insn.transitions().count())];
     while let Some((idx, vm_state, num_transitions)) = worklist.pop() {
@@ -83,75 +168,197 @@ fn unroll_dfa(_lfs: &LoadedFiles, cp: &CompiledProgram) -> BrainWithSymbols {
 
             let mut vm_state2 = vm_state.clone();
             vm_state2.give_input(input);
-            let outputs = run_to_input(&mut vm_state2, cp);
-            let insn2 = outputs_to_insn(&outputs);
+            let (insn2, comment) = run_to_input(&mut vm_state2, lfs, cp)?;
 
             idx_map.entry((vm_state2, insn2)).and_modify(|&mut idx2| {
                 nodes[idx].edges.push((state, idx2));
             }).or_insert_with_key(|(vm_state2, insn2)| {
                 let idx2 = nodes.len();
-                nodes.push(Node::new(*insn2, format!("{:?}", vm_state2.globals)));
+                nodes.push(Node::new(*insn2, comment));
                 worklist.push((idx2, vm_state2.clone(), insn2.transitions().count()));
                 nodes[idx].edges.push((state, idx2));
                 idx2
             });
+            if nodes.len() > max_states {
+                let report = explosion_report(idx_map.keys().map(|(s, _)| s), cp);
+                return Err(UnrollError::TooManyStates(report));
+            }
         }
     }
 
-    println!();
-    for (i, node) in nodes.iter().enumerate() {
-        println!("{:>4}:  {}  ; {}", i, node.insn, node.comment);
+    Ok(AnnotatedBrain(nodes.into_iter()
+        .map(|node| (node.patched_insn(), node.comment))
+        .collect()))
+}
+
+// Compiles the ant with the preludes, exits on errors.
+// Modules are also searched next to the ant and in data/py.
+pub fn load_program(prelude_filenames: &[String], input_filename: &str, mut search_dirs: Vec<PathBuf>)
+-> (LoadedFiles, CompiledProgram) {
+    let mut files = vec![];
+    for filename in prelude_filenames.iter().map(|s| s.as_str()).chain(std::iter::once(input_filename)) {
+        let text = std::fs::read_to_string(filename).unwrap_or_else(|e| {
+            println!("can't read {}: {}", filename, e);
+            std::process::exit(1);
+        });
+        files.push((filename, text));
+    }
+    let files: Vec<(&str, &str)> = files.iter()
+        .map(|(filename, text)| (*filename, text.as_str()))
+        .collect();
+    let mut lfs = LoadedFiles::new(&files);
+    search_dirs.push(Path::new(input_filename).parent().unwrap().to_path_buf());
+    search_dirs.push(Path::new("data/py").to_path_buf());
+    for dir in search_dirs {
+        lfs.add_search_dir(dir);
     }
-    println!();
 
-    BrainWithSymbols {
-        insns: nodes.iter().map(|node| node.patched_insn()).collect(),
+    match CompiledProgram::new(&mut lfs) {
+        Ok(cp) => (lfs, cp),
+        Err(e) => {
+            println!("{}", lfs.render_error(e));
+            std::process::exit(1);
+        }
     }
 }
 
 // ENTRY_POINT
 pub fn py2ant() {
     let args: Vec<String> = std::env::args().collect();
-    let input_filename = match &args[..] {
-        [_, _, input] => input,
-        _ => {
-            println!("Usage:");
-            println!("    py2ant data/py/example.py");
-            std::process::exit(1);
+    fn usage() -> ! {
+        println!("Usage:");
+        println!("    py2ant [--max-states N] [--prelude FILE]... [-I DIR]... data/py/example.py");
+        println!("Modules are searched in the -I directories, then next to the input file, then in data/py.");
+        std::process::exit(1);
+    }
+    let mut max_states = MAX_STATES;
+    let mut prelude_filenames = vec!["data/py/_prelude.py".to_string()];
+    let mut search_dirs = vec![];
+    let mut rest = &args[2..];
+    let input_filename = loop {
+        match rest {
+            [flag, n, tail @ ..] if flag == "--max-states" => {
+                max_states = n.parse().unwrap_or_else(|_| usage());
+                rest = tail;
+            }
+            [flag, file, tail @ ..] if flag == "--prelude" => {
+                prelude_filenames.push(file.clone());
+                rest = tail;
+            }
+            [flag, dir, tail @ ..] if flag == "-I" => {
+                search_dirs.push(PathBuf::from(dir));
+                rest = tail;
+            }
+            [input] if !input.starts_with('-') => break input,
+            _ => usage(),
         }
     };
     println!("hello, {}", input_filename);
 
-    let prelude_text = std::fs::read_to_string("data/py/_prelude.py").unwrap();
-    let input_text = std::fs::read_to_string(input_filename).unwrap();
+    let (lfs, cp) = load_program(&prelude_filenames, input_filename, search_dirs);
+    println!("{}", cp);
 
-    let mut lfs = LoadedFiles::new(&[
-        ("prelude.py", &prelude_text),
-        (input_filename, &input_text),
-    ]);
+    let output_path = Path::new(input_filename).file_name().unwrap();
+    let output_path = Path::new("outputs").join(output_path);
+    let output_path = format!("{}.ant", output_path.to_str().unwrap());
 
-    let cp = CompiledProgram::new(&mut lfs);
-    let cp = match cp {
-        Ok(cp) => cp,
-        Err(e) => {
+    println!();
+    let b = match unroll_dfa(&lfs, &cp, max_states) {
+        Ok(b) => b,
+        Err(UnrollError::Runtime(e)) => {
             println!("{}", lfs.render_error(e));
             std::process::exit(1);
         }
+        Err(UnrollError::TooManyStates(report)) => {
+            println!("{}", report);
+            std::process::exit(1);
+        }
     };
+    println!("{:?}", b);
 
-    println!("{}", cp);
+    b.save_to_file(&output_path);
+    println!("saved to {}", output_path);
+}
 
-    let output_path = Path::new(input_filename).file_name().unwrap();
-    let output_path = Path::new("outputs").join(output_path);
-    let output_path = format!("{}.ant", output_path.to_str().unwrap());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!();
-    let b = unroll_dfa(&lfs, &cp);
-    for (i, insn) in b.insns.iter().enumerate() {
-        println!("{:>4}:  {}", i, insn);
+    fn unroll_error(text: &str) -> String {
+        let mut lfs = LoadedFiles::new(&[("a.py", text)]);
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
+        match unroll_dfa(&lfs, &cp, MAX_STATES) {
+            Err(UnrollError::Runtime(e)) => lfs.render_error(e),
+            _ => panic!(),
+        }
     }
 
-    println!();
-    std::fs::write(&output_path, neurology::dumps(&b.insns)).unwrap();
-    println!("saved to {}", output_path);
+    #[test]
+    fn not_an_instruction() {
+        assert_eq!(unroll_error("\
+def main():
+    _output(5)
+    _input()
+main()
+"), "\
+a.py:2:12  runtime error: expected a single instruction before _input(), got [5]
+        _output(5)
+               ^");
+    }
+
+    #[test]
+    fn bad_instruction_string() {
+        assert_eq!(unroll_error("\
+while True:
+    _output('sence ahead 1 0 food')
+    _input()
+"), "\
+a.py:2:12  runtime error: unknown command \"sence\" in instruction \"sence ahead 1 0 food\"
+        _output('sence ahead 1 0 food')
+               ^");
+    }
+
+    #[test]
+    fn state_cap() {
+        // n is only needed until the first loop is done,
+        // but it multiplies the states of the second one
+        let program = |forget: &str| format!("\
+n = 0
+while n < 20:
+    _output('flip 2 0 1')
+    if _input() == 0:
+        break
+    n += 1
+{}
+while True:
+    _output('turn left 0')
+    _input()
+    _output('move 0 0')
+    _input()
+", forget);
+        let unroll = |text: &str| {
+            let mut lfs = LoadedFiles::new(&[("a.py", text)]);
+            let cp = CompiledProgram::new(&mut lfs).unwrap();
+            unroll_dfa(&lfs, &cp, 40)
+        };
+        match unroll(&program("")) {
+            Err(UnrollError::TooManyStates(report)) => {
+                let lines: Vec<&str> = report.lines().collect();
+                assert!(lines[2].ends_with("  n"), "{}", report);
+            }
+            _ => panic!(),
+        }
+        match unroll(&program("_forget(n)")) {
+            Ok(b) => assert!(b.0.len() <= 40),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn program_ends() {
+        assert_eq!(unroll_error("x = 1\n"), "\
+a.py:1:3  runtime error: ant program ended
+    x = 1
+      ^");
+    }
 }
@@ -0,0 +1,376 @@
+// Runs .py ants directly in the simulator, without unrolling them
+// into state machines first, with breakpoints on Python lines.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use crate::cartography::World;
+use crate::neurology::{Instruction, State, parse_ant};
+use crate::number_theory::Random;
+use crate::py::compiler::{LoadedFiles, CompiledProgram};
+use crate::py::vm::{self, Status, Value};
+use crate::py2ant::{load_program, outputs_to_insn, is_constant};
+
+pub enum DebugBrain {
+    StateMachine(Vec<Instruction>),
+    Py(LoadedFiles, CompiledProgram),
+}
+
+// The VM outputs the instruction for the next turn of the ant
+// and gets the state the simulator chose as input.
+struct PyAnt {
+    vm: vm::State,
+    insn: Option<Instruction>,  // waiting for the simulator
+    line: Option<(String, usize)>,  // of the last executed VM instruction
+}
+
+pub enum Stop {
+    Breakpoint(u8),  // ant id
+    EndOfMatch,
+}
+
+pub struct Debugger {
+    pub world: World,
+    rng: Random,
+    brains: [DebugBrain; 2],
+    py_ants: HashMap<u8, PyAnt>,
+    pub round: usize,
+    next_id: u8,  // ant to move next in this round
+    breakpoints: Vec<(String, usize)>,  // file name or its suffix, line
+}
+
+impl Debugger {
+    pub fn new(world: World, brains: [DebugBrain; 2], seed: u32) -> Self {
+        Debugger {
+            world,
+            rng: Random::new(seed),
+            brains,
+            py_ants: HashMap::new(),
+            round: 0,
+            next_id: 0,
+            breakpoints: vec![],
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, file: &str, line: usize) {
+        self.breakpoints.push((file.to_string(), line));
+    }
+
+    pub fn remove_breakpoint(&mut self, file: &str, line: usize) -> bool {
+        let len = self.breakpoints.len();
+        self.breakpoints.retain(|(f, l)| (f.as_str(), *l) != (file, line));
+        self.breakpoints.len() < len
+    }
+
+    // Moves the next ant. When it stops at a breakpoint in the middle
+    // of a py ant's turn, the next call continues that turn.
+    // Errors are rendered.
+    pub fn step_ant(&mut self) -> Result<Option<Stop>, String> {
+        if self.round == self.world.rules.rounds {
+            return Ok(Some(Stop::EndOfMatch));
+        }
+        let id = self.next_id;
+        let color = self.world.find_ant(id)
+            .and_then(|pos| self.world.ant_at(pos))
+            .map(|ant| ant.color);
+        if let Some(color) = color {
+            match &self.brains[color as usize] {
+                DebugBrain::StateMachine(insns) => {
+                    self.world.step_with(id, &mut self.rng, |ant| insns[ant.state.0 as usize]);
+                }
+                DebugBrain::Py(lfs, cp) => {
+                    let py_ant = self.py_ants.entry(id).or_insert_with(|| PyAnt {
+                        vm: vm::State::new(),
+                        insn: None,
+                        line: None,
+                    });
+                    if py_ant.insn.is_none() && !run_py_ant(py_ant, lfs, cp, &self.breakpoints)? {
+                        return Ok(Some(Stop::Breakpoint(id)));
+                    }
+                    let insn = py_ant.insn.unwrap();
+                    let mut executed = false;
+                    self.world.step_with(id, &mut self.rng, |_| {
+                        executed = true;
+                        insn
+                    });
+                    let world = &self.world;
+                    let state = world.find_ant(id)
+                        .and_then(|pos| world.ant_at(pos))
+                        .map(|ant| ant.state);
+                    if let (true, Some(State(state))) = (executed, state) {
+                        py_ant.vm.give_input(Value::Int(state.into()));
+                        py_ant.insn = None;
+                    }
+                }
+            }
+        }
+
+        // there are two anthills, 91 ants max each
+        self.next_id += 1;
+        if self.next_id == 182 {
+            self.next_id = 0;
+            self.round += 1;
+            self.world.round += 1;
+        }
+        Ok(None)
+    }
+
+    // Runs until a breakpoint, the end of the match,
+    // or the start of the round that is num_rounds away.
+    pub fn run(&mut self, num_rounds: Option<usize>) -> Result<Option<Stop>, String> {
+        let end_round = num_rounds.map(|n| self.round + n);
+        while Some(self.round) != end_round {
+            if let Some(stop) = self.step_ant()? {
+                return Ok(Some(stop));
+            }
+        }
+        Ok(None)
+    }
+
+    // Position and the rest of the ant state, plus
+    // Python call stack and variables for py ants.
+    pub fn describe_ant(&self, id: u8) -> String {
+        use std::fmt::Write;
+        let ant = match self.world.find_ant(id).and_then(|pos| self.world.ant_at(pos)) {
+            Some(ant) => ant,
+            None => return format!("ant {} is dead", id),
+        };
+        let mut res = format!("ant {} ({:?}) at {:?} facing {:?}, state {}",
+            id, ant.color, self.world.find_ant(id).unwrap(), ant.direction, ant.state);
+        if ant.has_food {
+            res.push_str(", carrying food");
+        }
+        if ant.resting > 0 {
+            write!(res, ", resting {}", ant.resting).unwrap();
+        }
+        if let (DebugBrain::Py(lfs, cp), Some(py_ant)) = (&self.brains[ant.color as usize], self.py_ants.get(&id)) {
+            write!(res, "\n  {}", py_ant.vm.backtrace(cp, lfs)).unwrap();
+            if let Some(insn) = py_ant.insn {
+                write!(res, "\n  next: {}", insn).unwrap();
+            }
+            for (name, value) in py_ant.vm.variables(cp) {
+                if !is_constant(&name) {
+                    write!(res, "\n  {} = {:?}", name, value).unwrap();
+                }
+            }
+        }
+        res
+    }
+}
+
+// Runs the VM until it outputs an instruction.
+// Returns false if stopped at a breakpoint.
+fn run_py_ant(
+    py_ant: &mut PyAnt,
+    lfs: &LoadedFiles,
+    cp: &CompiledProgram,
+    breakpoints: &[(String, usize)],
+) -> Result<bool, String> {
+    let mut outputs = vec![];
+    let mut output_loc = None;
+    loop {
+        let next_loc = py_ant.vm.next_loc(cp);
+        if let Some(loc) = next_loc {
+            let (filename, line) = lfs.file_line(loc);
+            let same_line = py_ant.line.as_ref()
+                .is_some_and(|(f, l)| (f.as_str(), *l) == (filename, line));
+            if !same_line {
+                // remembered before stopping, so resuming doesn't stop here again
+                py_ant.line = Some((filename.to_string(), line));
+                let hit = breakpoints.iter().any(|(f, l)| {
+                    *l == line && (filename == f || filename.ends_with(&format!("/{}", f)))
+                });
+                if hit {
+                    return Ok(false);
+                }
+            }
+        }
+        let num_outputs = outputs.len();
+        let status = py_ant.vm.step(cp, &mut outputs).map_err(|e| lfs.render_error(e))?;
+        if outputs.len() > num_outputs {
+            output_loc = next_loc;
+        }
+        match status {
+            Status::Running => {}
+            Status::BlockedOnInput => {
+                let loc = output_loc.or(next_loc).unwrap();
+                py_ant.insn = Some(outputs_to_insn(&outputs, loc, cp).map_err(|e| lfs.render_error(e))?);
+                return Ok(true);
+            }
+            Status::Terminated => return Err("ant program terminated, it should be an infinite loop".to_string()),
+        }
+    }
+}
+
+fn load_brain(filename: &str) -> DebugBrain {
+    if filename.ends_with(".py") {
+        let (lfs, cp) = load_program(&["data/py/_prelude.py".to_string()], filename, vec![]);
+        DebugBrain::Py(lfs, cp)
+    } else {
+        DebugBrain::StateMachine(parse_ant(&std::fs::read_to_string(filename).unwrap()))
+    }
+}
+
+fn parse_file_line(s: &str) -> Option<(&str, usize)> {
+    let (file, line) = s.rsplit_once(':')?;
+    Some((file, line.parse().ok()?))
+}
+
+const HELP: &str = "\
+b FILE:LINE    set breakpoint (FILE can be a suffix of the path)
+d FILE:LINE    delete breakpoint
+b              list breakpoints
+s              move one ant
+r [N]          run N rounds (default 1)
+c              run until a breakpoint or the end of the match
+p ID           show ant, with Python stack and variables for py ants
+w              show the world and the score
+q              quit";
+
+// ENTRY_POINT
+pub fn pydebug() {
+    let args: Vec<String> = std::env::args().collect();
+    let (seed, rest) = match &args[2..] {
+        [flag, n, rest @ ..] if flag == "--seed" => match n.parse() {
+            Ok(seed) => (seed, rest),
+            Err(_) => (0, &[][..]),
+        },
+        rest => (12345, rest),
+    };
+    let (world_filename, red, black) = match rest {
+        [world, red, black] => (world, red, black),
+        _ => {
+            println!("Usage:");
+            println!("    pydebug [--seed N] data/tiny.world red.py black.ant");
+            println!("Either ant can be .py or .ant.");
+            std::process::exit(1);
+        }
+    };
+
+    let world = World::from_map_string(&std::fs::read_to_string(world_filename).unwrap());
+    let mut debugger = Debugger::new(world, [load_brain(red), load_brain(black)], seed);
+    println!("{}", HELP);
+
+    let stdin = std::io::stdin();
+    loop {
+        print!("(round {}, ant {}) ", debugger.round, debugger.next_id);
+        std::io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let res = match words[..] {
+            [] => continue,
+            ["q"] => break,
+            ["b"] => {
+                for (f, l) in &debugger.breakpoints {
+                    println!("{}:{}", f, l);
+                }
+                continue;
+            }
+            ["b", loc] | ["d", loc] => {
+                match parse_file_line(loc) {
+                    Some((file, line)) if words[0] == "b" => debugger.add_breakpoint(file, line),
+                    Some((file, line)) => if !debugger.remove_breakpoint(file, line) {
+                        println!("no such breakpoint");
+                    }
+                    None => println!("expected FILE:LINE"),
+                }
+                continue;
+            }
+            ["p", id] => {
+                match id.parse() {
+                    Ok(id) => println!("{}", debugger.describe_ant(id)),
+                    Err(_) => println!("expected ant id"),
+                }
+                continue;
+            }
+            ["w"] => {
+                println!("{}", debugger.world);
+                println!("score: red {}, black {}",
+                    debugger.world.food_at_anthill(crate::biology::Color::Red).0,
+                    debugger.world.food_at_anthill(crate::biology::Color::Black).0);
+                continue;
+            }
+            ["s"] => debugger.step_ant(),
+            ["r"] => debugger.run(Some(1)),
+            ["r", n] => match n.parse() {
+                Ok(n) => debugger.run(Some(n)),
+                Err(_) => {
+                    println!("expected number of rounds");
+                    continue;
+                }
+            },
+            ["c"] => debugger.run(None),
+            _ => {
+                println!("{}", HELP);
+                continue;
+            }
+        };
+        match res {
+            Ok(None) => {}
+            Ok(Some(Stop::Breakpoint(id))) => {
+                let (file, line) = debugger.py_ants[&id].line.as_ref().unwrap();
+                println!("breakpoint at {}:{}", file, line);
+                println!("{}", debugger.describe_ant(id));
+            }
+            Ok(Some(Stop::EndOfMatch)) => println!("end of match"),
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biology::Color;
+    use crate::geometry::Dir;
+
+    #[test]
+    fn breakpoints() {
+        let world = World::from_map_string(
+            &std::fs::read_to_string("data/tiny.world").unwrap());
+        let mut lfs = LoadedFiles::new(&[("a.py", "\
+n = 0
+while True:
+    _output('turn left 0')
+    _input()
+    n += 1
+")]);
+        let cp = CompiledProgram::new(&mut lfs).unwrap();
+        let brains = [
+            DebugBrain::Py(lfs, cp),
+            DebugBrain::StateMachine(parse_ant("Turn Right 0")),
+        ];
+        let mut debugger = Debugger::new(world, brains, 42);
+        debugger.add_breakpoint("a.py", 5);
+
+        // the first turn is executed before reaching the breakpoint
+        let id = match debugger.run(None).unwrap() {
+            Some(Stop::Breakpoint(id)) => id,
+            _ => panic!(),
+        };
+        assert_eq!(debugger.round, 1);
+        let ant = debugger.world.ant_at(debugger.world.find_ant(id).unwrap()).unwrap();
+        assert_eq!(ant.color, Color::Red);
+        assert_eq!(ant.direction, Dir::NE);
+        assert_eq!(debugger.py_ants[&id].vm.globals["n"], Value::Int(0));
+
+        // resuming doesn't stop at the same place
+        match debugger.run(None).unwrap() {
+            Some(Stop::Breakpoint(id2)) => assert_ne!(id2, id),
+            _ => panic!(),
+        }
+
+        assert!(debugger.remove_breakpoint("a.py", 5));
+        assert!(debugger.run(Some(2)).unwrap().is_none());
+        assert_eq!(debugger.round, 3);
+        let ant = debugger.world.ant_at(debugger.world.find_ant(id).unwrap()).unwrap();
+        assert_eq!(ant.direction, Dir::W);
+        assert_eq!(debugger.py_ants[&id].vm.globals["n"], Value::Int(2));
+        assert!(debugger.describe_ant(id).contains("n = 2"));
+    }
+}
@@ -107,12 +107,12 @@ impl CompilerCtx {
 
 // global variable go BRRRR. Prefer convenience to slight unsafety.
 thread_local! {
-    static CTX : RefCell<Option<CompilerCtx>> = RefCell::new(None);
+    static CTX : RefCell<Option<CompilerCtx>> = const { RefCell::new(None) };
 }
 
 fn with_ctx<T>(f: impl FnOnce(&mut CompilerCtx) -> T) -> T {
     CTX.with(|ctx| -> T {
-        f(&mut ctx.borrow_mut().as_mut().expect("Call compile()"))
+        f(ctx.borrow_mut().as_mut().expect("Call compile()"))
     })
 }
 
@@ -0,0 +1,139 @@
+// Parameter sweep over magic ant templates.
+//
+// Compiles the template once for every combination of its param values,
+// plays each brain against the opponents like evolution::Arena does,
+// and prints the combinations ranked by score.
+
+use crate::magic::{Template, compile_with_params};
+use crate::neurology::Brain;
+use crate::evolution::Arena;
+use crate::bouncing_ant;
+use crate::magic_examples;
+
+const TEMPLATES: &[&Template] = &[
+    &bouncing_ant::TEMPLATE,
+    &magic_examples::SPIRAL_TEMPLATE,
+];
+
+// All combinations of the param values, the last param varies fastest.
+pub fn combinations(params: &[(&'static str, &[i32])]) -> Vec<Vec<(&'static str, i32)>> {
+    let mut res = vec![vec![]];
+    for &(name, values) in params {
+        res = res.into_iter()
+            .flat_map(|combo: Vec<(&'static str, i32)>| values.iter().map(move |&v| {
+                let mut combo = combo.clone();
+                combo.push((name, v));
+                combo
+            }))
+            .collect();
+    }
+    res
+}
+
+pub struct SweepResult {
+    pub values: Vec<(&'static str, i32)>,
+    pub brain: Brain,
+    pub score: usize,
+}
+
+// Best first, ties keep the declaration order.
+pub fn sweep(template: &Template, arena: &Arena) -> Vec<SweepResult> {
+    let mut results: Vec<SweepResult> = combinations(template.params).into_iter().map(|values| {
+        let brain = Brain::from(&compile_with_params(template.ant, &values));
+        let score = arena.fitness(&brain);
+        println!("{:?}: {} of {}", values, score, arena.max_fitness());
+        SweepResult { values, brain, score }
+    }).collect();
+    results.sort_by_key(|r| std::cmp::Reverse(r.score));
+    results
+}
+
+pub fn render_table(results: &[SweepResult], max_score: usize) -> String {
+    let mut header = vec!["rank".to_string(), "score".to_string(), "states".to_string()];
+    header.extend(results[0].values.iter().map(|(name, _)| name.to_string()));
+    let mut rows = vec![header];
+    for (rank, r) in results.iter().enumerate() {
+        let mut row = vec![
+            (rank + 1).to_string(),
+            format!("{}/{}", r.score, max_score),
+            r.brain.states.len().to_string(),
+        ];
+        row.extend(r.values.iter().map(|(_, v)| v.to_string()));
+        rows.push(row);
+    }
+    let widths: Vec<usize> = (0..rows[0].len())
+        .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap())
+        .collect();
+    let mut res = String::new();
+    for row in rows {
+        let cells: Vec<String> = row.iter().zip(&widths)
+            .map(|(cell, &w)| format!("{:>w$}", cell, w = w))
+            .collect();
+        res.push_str(&cells.join("  "));
+        res.push('\n');
+    }
+    res
+}
+
+// ENTRY_POINT
+pub fn sweep_params() {
+    let args: Vec<String> = std::env::args().collect();
+    fn usage() -> ! {
+        println!("Usage:");
+        println!("    sweep_params [--worlds N] template opponent.ant...");
+        let names: Vec<&str> = TEMPLATES.iter().map(|t| t.name).collect();
+        println!("Templates: {}", names.join(", "));
+        std::process::exit(1);
+    }
+    let (num_worlds, rest) = match &args[2..] {
+        [flag, n, rest @ ..] if flag == "--worlds" => (n.parse().unwrap_or_else(|_| usage()), rest),
+        rest => (1, rest),
+    };
+    let (template, opponents) = match rest {
+        [name, opponents @ ..] if !opponents.is_empty() => {
+            match TEMPLATES.iter().find(|t| t.name == name) {
+                Some(t) => (t, opponents),
+                None => usage(),
+            }
+        }
+        _ => usage(),
+    };
+
+    let arena = Arena::new(num_worlds, opponents);
+    let results = sweep(template, &arena);
+    println!();
+    print!("{}", render_table(&results, arena.max_fitness()));
+
+    let best = &results[0];
+    let mut brain = best.brain.clone();
+    brain.states[0].leading = vec![format!("; {} with {:?}, score {} of {}",
+        template.name, best.values, best.score, arena.max_fitness())];
+    let path = format!("outputs/{}_best.ant", template.name);
+    std::fs::write(&path, brain.dumps()).unwrap();
+    println!("saved to {}", path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_all_combinations() {
+        assert_eq!(combinations(&[("a", &[1, 2]), ("b", &[3, 4, 5])]).len(), 6);
+        assert_eq!(combinations(&[("a", &[1, 2]), ("b", &[3])]),
+            [[("a", 1), ("b", 3)], [("a", 2), ("b", 3)]]);
+
+        let default = Brain::from(&crate::magic::compile(magic_examples::SPIRAL_TEMPLATE.ant));
+        let mut sizes = vec![];
+        for values in combinations(magic_examples::SPIRAL_TEMPLATE.params) {
+            let brain = Brain::from(&compile_with_params(magic_examples::SPIRAL_TEMPLATE.ant, &values));
+            if values == [("max_k", 4), ("growth", 2)] {
+                assert_eq!(brain, default);
+            }
+            sizes.push(brain.states.len());
+        }
+        // moves for each k, a turn after each, and the final drop,
+        // e.g. k = 1, 2, 4 is 7 moves + 3 turns + 1 drop for max_k = 4, growth = 2
+        assert_eq!(sizes, [6, 3, 11, 7, 20, 7]);
+    }
+}
@@ -0,0 +1,146 @@
+// Variants of a world with the same geometry.
+//
+// match_pair() swaps the brains to even out the colours, but each colour
+// still plays from its own corner of the map. Playing the same pairing on
+// a mirrored or rotated copy as well evens out directions too, since ants
+// always start facing E.
+//
+// Cells are moved in Hex coordinates, so neighbours stay neighbours
+// whatever the row parity. The result is shifted back in place and
+// padded with rocks where needed, e.g. a mirrored map is one column
+// wider since odd rows are shifted right. The ants are fresh, numbered
+// as if the map was loaded from a file.
+
+use std::collections::HashMap;
+
+use crate::biology::other_color;
+use crate::cartography::World;
+use crate::geography::MapToken;
+use crate::geometry::{Dir, Hex, Pos};
+
+fn transformed(world : &World, f : impl Fn(Hex) -> Hex, swap_colors : bool) -> World {
+    let cells : Vec<(Hex, &MapToken)> = world.data.iter()
+        .map(|(&p, token)| (f(Hex::from(p)), token))
+        .collect();
+    let min_r = cells.iter().map(|(h, _)| h.r).min().unwrap();
+    let cells : Vec<(Hex, &MapToken)> = cells.into_iter()
+        .map(|(h, token)| (Hex{ q: h.q, r: h.r - min_r }, token))
+        .collect();
+    // x in offset coordinates, see Hex::to_pos()
+    let min_x = cells.iter().map(|(h, _)| h.q + (h.r - (h.r & 1)) / 2).min().unwrap();
+
+    let mut data : HashMap<Pos, MapToken> = HashMap::new();
+    for (h, token) in &cells {
+        let mut token = (*token).clone();
+        if let MapToken::Clear(cont) = &mut token {
+            cont.ant = None;
+            if swap_colors {
+                cont.anthill = cont.anthill.map(other_color);
+            }
+        }
+        data.insert((Hex{ q: h.q - min_x, r: h.r }).to_pos().unwrap(), token);
+    }
+
+    // one rock column on each side is enough,
+    // so that mirroring twice gives the same map back
+    let rocky_column = |data : &HashMap<Pos, MapToken>, cx| {
+        data.iter().all(|(p, token)| p.x != cx || matches!(token, MapToken::Rock))
+    };
+    while rocky_column(&data, 0) && rocky_column(&data, 1) {
+        data = data.into_iter().filter(|(p, _)| p.x > 0).map(|(p, token)| (Pos{ x: p.x - 1, ..p }, token)).collect();
+    }
+    loop {
+        let last = data.keys().map(|p| p.x).max().unwrap();
+        if !(rocky_column(&data, last) && rocky_column(&data, last - 1)) {
+            break;
+        }
+        data.retain(|p, _| p.x != last);
+    }
+
+    let x = data.keys().map(|p| p.x).max().unwrap() + 1;
+    let y = data.keys().map(|p| p.y).max().unwrap() + 1;
+    let mut res = World::framed(x, y);
+    res.data.extend(data);
+    for cx in 0..x {
+        for cy in 0..y {
+            res.data.entry(Pos{ x: cx, y: cy }).or_insert(MapToken::Rock);
+        }
+    }
+    let mut res = World::from_map_string(&res.to_map_string());
+    res.rules = world.rules;
+    res
+}
+
+// Left and right swapped.
+pub fn mirrored(world : &World) -> World {
+    transformed(world, Hex::mirror, false)
+}
+
+// Turned around, so the top left corner ends up bottom right.
+pub fn rotated(world : &World) -> World {
+    transformed(world, |h| h.rotate(Dir::W), false)
+}
+
+// Red anthills become black ones and the other way around.
+pub fn colors_swapped(world : &World) -> World {
+    transformed(world, |h| h, true)
+}
+
+// The world itself first, then the other three you get from
+// mirroring and rotating. match_pair() takes care of the colours.
+pub fn variants(world : &World) -> Vec<World> {
+    let mirror = mirrored(world);
+    vec![world.clone(), rotated(world), rotated(&mirror), mirror]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biology::Color::*;
+    use crate::fairness::analyse;
+    use crate::topography::DistanceField;
+
+    fn tiny() -> World {
+        World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap())
+    }
+
+    #[test]
+    fn tiny_is_symmetric() {
+        let world = tiny();
+        assert_eq!(world.to_map_string(), World::from_map_string(&world.to_map_string()).to_map_string());
+        assert_eq!(colors_swapped(&rotated(&world)).to_map_string(), world.to_map_string());
+        assert_ne!(rotated(&world).to_map_string(), world.to_map_string());
+        assert_eq!(rotated(&rotated(&world)).to_map_string(), world.to_map_string());
+    }
+
+    #[test]
+    fn variants_keep_geometry() {
+        let world = World::from_map_string(&std::fs::read_to_string("data/sample3.world").unwrap());
+        let report = analyse(&world);
+        let swapped = analyse(&colors_swapped(&world));
+        assert_eq!(swapped.red, report.black);
+        assert_eq!(swapped.asymmetry, -report.asymmetry);
+
+        for (i, v) in variants(&world).iter().enumerate() {
+            assert_eq!(v.ant_positions.len(), world.ant_positions.len());
+            assert_eq!(v.food_at_anthill(Red).0, 0);
+            let r = analyse(v);
+            assert_eq!((&r.red, &r.black, r.hill_distance), (&report.red, &report.black, report.hill_distance), "{}", i);
+            // ants are numbered in reading order, like when loading a map
+            let first = v.find_ant(0).unwrap();
+            assert!(v.ant_positions.values().all(|p| (p.y, p.x) >= (first.y, first.x)));
+        }
+        let mirror = mirrored(&world);
+        assert_eq!((mirror.x, mirror.y), (world.x + 1, world.y));
+        assert_eq!(mirrored(&mirror).to_map_string(), world.to_map_string());
+
+        // neighbours stay neighbours
+        let field = DistanceField::from_anthill(&world, Red);
+        let mirror_field = DistanceField::from_anthill(&mirror, Red);
+        let mut a : Vec<usize> = field.0.values().cloned().collect();
+        let mut b : Vec<usize> = mirror_field.0.values().cloned().collect();
+        a.sort();
+        b.sort();
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,145 @@
+// Distances over a world, going around rocks.
+//
+// Ants are ignored since they move out of the way eventually,
+// so these are lower bounds for how long a trip actually takes.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::biology::Color;
+use crate::cartography::World;
+use crate::geometry::{Dir, Pos, adj};
+use crate::prelude::simple_enum_iter;
+
+// Steps from every cell to the nearest source, unreachable cells are left out.
+#[derive(Clone, Debug)]
+pub struct DistanceField(pub HashMap<Pos, usize>);
+
+impl DistanceField {
+    pub fn new(world : &World, sources : impl IntoIterator<Item=Pos>) -> DistanceField {
+        let mut dist = HashMap::new();
+        let mut queue = VecDeque::new();
+        for p in sources {
+            if passable(world, p) && !dist.contains_key(&p) {
+                dist.insert(p, 0);
+                queue.push_back(p);
+            }
+        }
+        while let Some(p) = queue.pop_front() {
+            let d = dist[&p];
+            for dir in simple_enum_iter::<Dir>(6) {
+                if let Some(a) = adj(p, dir) {
+                    if passable(world, a) && !dist.contains_key(&a) {
+                        dist.insert(a, d + 1);
+                        queue.push_back(a);
+                    }
+                }
+            }
+        }
+        DistanceField(dist)
+    }
+
+    pub fn from_anthill(world : &World, c : Color) -> DistanceField {
+        DistanceField::new(world, world.data.keys().cloned().filter(|&p| world.anthill_at(p, c)))
+    }
+
+    pub fn from_food(world : &World) -> DistanceField {
+        DistanceField::new(world, food_cells(world))
+    }
+
+    pub fn get(&self, p : Pos) -> Option<usize> {
+        self.0.get(&p).copied()
+    }
+
+    // From p to the nearest source, both included. Among equally short
+    // paths, the one taking the first direction in Dir order at each step.
+    pub fn path_from(&self, p : Pos) -> Option<Vec<Pos>> {
+        let mut d = self.get(p)?;
+        let mut path = vec![p];
+        let mut p = p;
+        while d > 0 {
+            p = simple_enum_iter::<Dir>(6)
+                .filter_map(|dir| adj(p, dir))
+                .find(|a| self.get(*a) == Some(d - 1))
+                .unwrap();
+            path.push(p);
+            d -= 1;
+        }
+        Some(path)
+    }
+}
+
+fn passable(world : &World, p : Pos) -> bool {
+    world.data.contains_key(&p) && !world.rocky(p)
+}
+
+pub fn shortest_path(world : &World, from : Pos, to : Pos) -> Option<Vec<Pos>> {
+    DistanceField::new(world, vec![to]).path_from(from)
+}
+
+// Sorted.
+pub fn food_cells(world : &World) -> Vec<Pos> {
+    let mut res : Vec<Pos> = world.data.keys().cloned().filter(|&p| world.food_at(p).0 > 0).collect();
+    res.sort();
+    res
+}
+
+// Food cells the colour can get to from its anthill, sorted.
+pub fn reachable_food(world : &World, c : Color) -> Vec<Pos> {
+    let field = DistanceField::from_anthill(world, c);
+    food_cells(world).into_iter().filter(|&p| field.get(p).is_some()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::biology::Color::*;
+
+    #[test]
+    fn walled_in() {
+        let world = World::from_map_string("\
+7
+5
+# # # # # # #
+ # + . # 5 . #
+# . . # # . #
+ # . . # 3 - #
+# # # # # # #
+");
+        let at = |x, y| Pos{x, y};
+        assert_eq!(food_cells(&world), [at(4, 1), at(4, 3)]);
+        assert_eq!(reachable_food(&world, Red), []);
+        assert_eq!(reachable_food(&world, Black), [at(4, 1), at(4, 3)]);
+
+        let black = DistanceField::from_anthill(&world, Black);
+        assert_eq!(black.get(at(5, 3)), Some(0));
+        assert_eq!(black.get(at(4, 3)), Some(1));
+        assert_eq!(black.get(at(4, 1)), Some(2));
+        assert_eq!(black.get(at(1, 1)), None);
+        assert_eq!(black.get(at(3, 2)), None);
+
+        assert_eq!(shortest_path(&world, at(5, 1), at(4, 3)), Some(vec![at(5, 1), at(5, 2), at(4, 3)]));
+        assert_eq!(shortest_path(&world, at(1, 1), at(4, 3)), None);
+        assert_eq!(DistanceField::from_food(&world).path_from(at(5, 3)), Some(vec![at(5, 3), at(4, 3)]));
+    }
+
+    #[test]
+    fn around_rocks() {
+        let world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let hill : Vec<Pos> = world.data.keys().cloned().filter(|&p| world.anthill_at(p, Red)).collect();
+        let field = DistanceField::from_anthill(&world, Red);
+        assert_eq!(reachable_food(&world, Red), food_cells(&world));
+        for (&p, &d) in &field.0 {
+            assert!(!world.rocky(p));
+            // never shorter than as the crow flies
+            assert!(d as u32 >= hill.iter().map(|h| h.distance(p)).min().unwrap());
+            let path = field.path_from(p).unwrap();
+            assert_eq!(path.len(), d + 1);
+            assert!(path.windows(2).all(|w| w[0].distance(w[1]) == 1));
+            assert!(hill.contains(path.last().unwrap()));
+        }
+        // 2 steps apart, but the rock at (2, 3) is in the way
+        let (from, to) = (Pos{x: 1, y: 3}, Pos{x: 3, y: 3});
+        assert_eq!(from.distance(to), 2);
+        assert_eq!(shortest_path(&world, from, to), Some(vec![from, Pos{x: 2, y: 4}, Pos{x: 3, y: 4}, to]));
+    }
+}
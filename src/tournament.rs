@@ -7,6 +7,8 @@ use crate::cartography::{
     World,
 };
 
+use crate::jurisprudence::Rules;
+
 use crate::neurology::{
     parse_ant,
     Instruction,
@@ -16,7 +18,7 @@ use crate::number_theory::{
     Random,
 };
 
-pub struct MatchScore(usize, usize);
+pub struct MatchScore(pub usize, pub usize);
 
 impl MatchScore {
 
@@ -26,8 +28,60 @@ impl MatchScore {
 
 }
 
+// True if food_at_anthill() can't change any more, whatever the ants do:
+// no kills are possible, nobody carries food, and every colour either
+// can't reach a PickUp from the states its ants are in, or has nothing
+// left to pick up, anthills included.
+pub fn score_is_frozen(world : &World, ant_brains : &[Vec<Instruction>; 2]) -> bool {
+    // an ant is killed by kill_threshold enemies around it,
+    // checked first and bailing out early since this runs every round
+    let threshold = world.rules.kill_threshold as usize;
+    // with 0 ants die with no enemies around, a lone colony included
+    if threshold == 0 {
+        return false;
+    }
+    let mut counts = [0, 0];
+    for &pos in world.ant_positions.values() {
+        counts[world.ant_at(pos).unwrap().color as usize] += 1;
+        let [red, black] = counts;
+        if (red >= threshold && black > 0) || (black >= threshold && red > 0) {
+            return false;
+        }
+    }
+    let mut states = [vec![], vec![]];
+    for &pos in world.ant_positions.values() {
+        let ant = world.ant_at(pos).unwrap();
+        if ant.has_food {
+            return false;
+        }
+        states[ant.color as usize].push(ant.state.0 as usize);
+    }
+    let can_pick_up = (0..2).any(|c| reaches_pick_up(&ant_brains[c], &states[c]));
+    !can_pick_up || world.data.keys().all(|&p| world.food_at(p).0 == 0)
+}
+
+fn reaches_pick_up(brain : &[Instruction], from : &[usize]) -> bool {
+    let mut seen = vec![false; brain.len()];
+    let mut todo = from.to_vec();
+    while let Some(s) = todo.pop() {
+        if seen[s] {
+            continue;
+        }
+        seen[s] = true;
+        if let Instruction::PickUp(..) = brain[s] {
+            return true;
+        }
+        todo.extend(brain[s].transitions().map(|t| t.0 as usize));
+    }
+    false
+}
+
+// Stops as soon as score_is_frozen(), world.round tells when.
 pub fn full_match(world : &mut World, ant_brains : &[Vec<Instruction>; 2], rng : &mut Random) -> Option<Color> {
-    for _ in 0..100000 {
+    for _ in 0..world.rules.rounds {
+        if score_is_frozen(world, ant_brains) {
+            break;
+        }
         world.round(ant_brains, rng);
     }
     let red_score = world.food_at_anthill(Red).0;
@@ -39,9 +93,10 @@ pub fn full_match(world : &mut World, ant_brains : &[Vec<Instruction>; 2], rng :
     }
 }
 
-// returns (red score, black score)
+// Adds the result of playing both sides to the score,
+// returns how many rounds each match took.
 pub fn match_pair(score : &mut MatchScore, world : &mut World,
-                  ant_brains : &mut [Vec<Instruction>; 2], rng : &mut Random) {
+                  ant_brains : &mut [Vec<Instruction>; 2], rng : &mut Random) -> [usize; 2] {
 
     let mut world_copy = world.clone();
     let mut rng_copy = rng.clone();
@@ -59,33 +114,41 @@ pub fn match_pair(score : &mut MatchScore, world : &mut World,
         Some(Black) => { score.0 += 2; },
         None => { score.0 += 1; score.1 += 1; },
     }
-
-
+    [world.round, world_copy.round]
 }
 
-// ENTRY_POINT
-pub fn tournament_ep() {
-    use std::fs;
+// data/*.world except the tiny one, sorted
+pub fn sample_worlds() -> Vec<String> {
     let mut worlds = Vec::new();
-    let mut seeds = vec![12345, 98765, 3566235, 375688, 864532, 42, 563845, 2071995, 8673, 35481];
     for p in std::fs::read_dir("data").unwrap() {
         let p = p.unwrap().path();
-        //println!("{:?}", p);
-        let pathstr = p.to_str().unwrap().to_string();
-        if p.extension().unwrap() == "world" && p.file_stem().unwrap().to_str().unwrap() != "tiny" {
-            worlds.push(pathstr);
+        if p.extension().is_some_and(|e| e == "world") && p.file_stem().unwrap() != "tiny" {
+            worlds.push(p.to_str().unwrap().to_string());
         }
     }
+    worlds.sort();
+    worlds
+}
+
+// ENTRY_POINT
+pub fn tournament_ep() {
+    use std::fs;
+    let worlds = sample_worlds();
+    let mut seeds = vec![12345, 98765, 3566235, 375688, 864532, 42, 563845, 2071995, 8673, 35481];
 
     let mut sum_score = MatchScore::new();
 
     let ant1 = std::env::args().nth(2).unwrap_or_else(|| "data/example_from_spec".to_string());
     let ant2 = std::env::args().nth(3).unwrap_or_else(|| "data/example_from_spec".to_string());
+    // e.g. "rest=10,kill=4", see Rules::parse()
+    let rules = Rules::parse(&std::env::args().nth(4).unwrap_or_default())
+        .unwrap_or_else(|e| panic!("bad rules: {}", e));
 
     for wpath in worlds {
         let w = fs::read_to_string(wpath.as_str())
             .expect("File not found or is broken");
         let mut w = World::from_map_string(&w);
+        w.rules = rules;
         let mut ant_brains = [
             parse_ant(&std::fs::read_to_string(format!("{}.ant", ant1)).unwrap()),
             parse_ant(&std::fs::read_to_string(format!("{}.ant", ant2)).unwrap()),
@@ -94,9 +157,12 @@ pub fn tournament_ep() {
 
         let mut wscore = MatchScore::new();
 
-        match_pair(&mut wscore, &mut w, &mut ant_brains, &mut rng);
+        let rounds = match_pair(&mut wscore, &mut w, &mut ant_brains, &mut rng);
 
         println!("Score on {} - {} {} : {} {}", wpath, ant1, wscore.0, wscore.1, ant2);
+        if rounds.iter().any(|&r| r < rules.rounds) {
+            println!("    decided early, after {} and {} rounds", rounds[0], rounds[1]);
+        }
 
         sum_score.0 += wscore.0;
         sum_score.1 += wscore.1;
@@ -105,3 +171,74 @@ pub fn tournament_ep() {
     println!("Final scores: {} {}, {} {}", ant1, sum_score.0, ant2, sum_score.1);
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geography::Food;
+
+    fn tiny_world() -> World {
+        World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap())
+    }
+
+    fn kill_colony(world : &mut World, color : Color) {
+        for (id, pos) in world.ant_positions.clone() {
+            if world.ant_at(pos).unwrap().color == color {
+                world.clear_ant_at(pos);
+                world.ant_positions.remove(&id);
+            }
+        }
+    }
+
+    #[test]
+    fn frozen_score() {
+        let wander = parse_ant("Move 0 1\nTurn Left 0");
+        let forage = parse_ant("Move 1 1\nPickUp 0 0");
+        let mut world = tiny_world();
+        // both colonies can still kill
+        assert!(!score_is_frozen(&world, &[wander.clone(), wander.clone()]));
+
+        kill_colony(&mut world, Black);
+        assert!(score_is_frozen(&world, &[wander.clone(), wander.clone()]));
+        assert!(!score_is_frozen(&world, &[forage.clone(), wander.clone()]));
+        // nobody left who could pick food up
+        assert!(score_is_frozen(&world, &[wander.clone(), forage.clone()]));
+        let mut no_threshold = world.clone();
+        no_threshold.rules.kill_threshold = 0;
+        assert!(!score_is_frozen(&no_threshold, &[wander.clone(), forage.clone()]));
+
+        let mut bare = world.clone();
+        for p in world.data.keys() {
+            bare.set_food_at(*p, Food(0));
+        }
+        assert!(score_is_frozen(&bare, &[forage.clone(), forage.clone()]));
+
+        // 4 ants of each colour can't surround anyone
+        let mut world = tiny_world();
+        let mut ids: Vec<u8> = world.ant_positions.keys().cloned().collect();
+        ids.sort();
+        for color in &[Red, Black] {
+            let ids: Vec<u8> = ids.iter().cloned().filter(|id| {
+                world.ant_positions.get(id).is_some_and(|&p| world.ant_at(p).unwrap().color == *color)
+            }).collect();
+            for &id in &ids[4..] {
+                world.clear_ant_at(world.ant_positions[&id]);
+                world.ant_positions.remove(&id);
+            }
+        }
+        assert!(score_is_frozen(&world, &[wander.clone(), wander.clone()]));
+        world.rules.kill_threshold = 4;
+        assert!(!score_is_frozen(&world, &[wander.clone(), wander]));
+    }
+
+    #[test]
+    fn full_match_stops_early() {
+        // PickUp only in state 0, which nobody comes back to
+        let brain = parse_ant("PickUp 1 1\nMove 1 1");
+        let mut world = tiny_world();
+        kill_colony(&mut world, Black);
+        let winner = full_match(&mut world, &[brain.clone(), brain], &mut Random::new(42));
+        assert_eq!(world.round, 1);
+        assert_eq!(winner, None);
+    }
+}
@@ -0,0 +1,176 @@
+// Zobrist hash of what changes during a game: ants, food and markers.
+//
+// World::hash is the xor of the keys of all of those, and every mutator
+// xors out the key of what it overwrites and xors in the new one, so it's
+// always up to date for free. Keys aren't a random table but a function
+// of the cell and the feature, so other implementations can compute the
+// same hashes:
+//
+//     key = splitmix64(x | y << 8 | kind << 16 | value << 24)
+//
+// kind 0 is an ant, value = id | color << 8 | state << 9 | resting << 25
+//     | direction << 33 | has_food << 36
+// kind 1 is food, value = amount, cells without food have no key
+// kind 2 is a marker, value = color * 6 + marker
+//
+// Red is color 0, directions are numbered like in the spec.
+// Rocks and anthills never change, so they're left out.
+
+use std::io::Write;
+use crate::biology::{Ant, Color};
+use crate::cartography::World;
+use crate::geography::{Food, MapToken};
+use crate::geometry::Pos;
+use crate::neurology::parse_ant;
+use crate::number_theory::Random;
+use crate::phenomenology::Marker;
+
+pub fn splitmix64(z: u64) -> u64 {
+    let z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn key(p: Pos, kind: u64, value: u64) -> u64 {
+    splitmix64(p.x as u64 | (p.y as u64) << 8 | kind << 16 | value << 24)
+}
+
+pub fn ant_key(p: Pos, ant: &Ant) -> u64 {
+    let value = ant.id as u64
+        | (ant.color as u64) << 8
+        | (ant.state.0 as u64) << 9
+        | (ant.resting as u64) << 25
+        | (ant.direction as u64) << 33
+        | (ant.has_food as u64) << 36;
+    key(p, 0, value)
+}
+
+pub fn food_key(p: Pos, food: Food) -> u64 {
+    if food.0 == 0 {
+        return 0;
+    }
+    key(p, 1, food.0 as u64)
+}
+
+pub fn marker_key(p: Pos, c: Color, m: Marker) -> u64 {
+    key(p, 2, c as u64 * 6 + m.0 as u64)
+}
+
+// From scratch, World::hash should always be equal to that.
+pub fn world_hash(world: &World) -> u64 {
+    let mut hash = 0;
+    for (&p, token) in &world.data {
+        if let MapToken::Clear(cont) = token {
+            if let Some(ant) = &cont.ant {
+                hash ^= ant_key(p, ant);
+            }
+            hash ^= food_key(p, cont.food);
+            for (&c, cms) in &cont.markers.0 {
+                for m in cms.into_iter() {
+                    hash ^= marker_key(p, c, Marker(m));
+                }
+            }
+        }
+    }
+    hash
+}
+
+// Index of the first difference, including one trace being shorter.
+pub fn first_divergence(a: &[u64], b: &[u64]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+// Hash before the first round and after each one.
+pub fn hash_trace(world: &mut World, ant_brains: &[Vec<crate::neurology::Instruction>; 2],
+                  rng: &mut Random, rounds: usize) -> Vec<u64> {
+    let mut res = vec![world.hash];
+    for _ in 0..rounds {
+        world.round(ant_brains, rng);
+        res.push(world.hash);
+    }
+    res
+}
+
+fn parse_trace(s: &str) -> Vec<u64> {
+    s.lines().map(|line| {
+        let (_round, hash) = line.split_once(' ').unwrap();
+        u64::from_str_radix(hash, 16).unwrap()
+    }).collect()
+}
+
+// ENTRY_POINT
+pub fn hash_trace_ep() {
+    let args: Vec<String> = std::env::args().collect();
+    fn usage() -> ! {
+        println!("Usage:");
+        println!("    hash_trace_ep map.world red.ant black.ant seed rounds [reference]");
+        println!("Writes \"round hash\" lines to outputs/hash_trace, round 0 is the initial world.");
+        println!("With a reference trace in that format, reports the first round they differ.");
+        std::process::exit(1);
+    }
+    let (map, red, black, seed, rounds, reference) = match &args[2..] {
+        [map, red, black, seed, rounds, reference @ ..] if reference.len() <= 1 =>
+            (map, red, black, seed, rounds, reference.first()),
+        _ => usage(),
+    };
+    let mut world = World::from_map_string(&std::fs::read_to_string(map).unwrap());
+    let ant_brains = [
+        parse_ant(&std::fs::read_to_string(red).unwrap()),
+        parse_ant(&std::fs::read_to_string(black).unwrap()),
+    ];
+    let mut rng = Random::new(seed.parse().unwrap_or_else(|_| usage()));
+    let trace = hash_trace(&mut world, &ant_brains, &mut rng, rounds.parse().unwrap_or_else(|_| usage()));
+
+    let mut out = vec![];
+    for (round, hash) in trace.iter().enumerate() {
+        writeln!(out, "{} {:016x}", round, hash).unwrap();
+    }
+    std::fs::write("outputs/hash_trace", out).unwrap();
+    println!("final hash {:016x}, written to outputs/hash_trace", trace.last().unwrap());
+
+    if let Some(reference) = reference {
+        let reference = parse_trace(&std::fs::read_to_string(reference).unwrap());
+        match first_divergence(&trace, &reference) {
+            Some(round) => println!("first divergence at round {}", round),
+            None => println!("identical"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incremental_hash() {
+        // the first splitmix64 output for seed 0
+        assert_eq!(splitmix64(0), 0xe220_a839_7b1d_cdaf);
+
+        let mut world = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let brain = parse_ant(&std::fs::read_to_string("data/sample.ant").unwrap());
+        let brains = [brain.clone(), brain];
+        let mut rng = Random::new(42);
+        let mut seen = std::collections::HashSet::new();
+        for _ in 0..500 {
+            assert_eq!(world.hash, world_hash(&world));
+            seen.insert(world.hash);
+            world.round(&brains, &mut rng);
+        }
+        assert!(seen.len() > 400);
+
+        let mut a = World::from_map_string(&std::fs::read_to_string("data/tiny.world").unwrap());
+        let mut b = a.clone();
+        let p = a.find_ant(0).unwrap();
+        b.set_marker_at(p, Color::Red, Marker(1));
+        let trace_a = hash_trace(&mut a, &brains, &mut Random::new(7), 20);
+        let trace_b = hash_trace(&mut b, &brains, &mut Random::new(7), 20);
+        assert_eq!(first_divergence(&trace_a, &trace_b), Some(0));
+        assert_eq!(first_divergence(&trace_a, &trace_a[..5]), Some(5));
+        assert_eq!(first_divergence(&trace_a, &trace_a), None);
+    }
+}